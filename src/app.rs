@@ -1,185 +1,944 @@
-use crate::aws::fetch_instances;
+use crate::aws::AwsError;
+use crate::aws::InventoryProvider;
+use crate::aws::InstanceStateChange;
+use crate::aws::RegionStatus;
 use crate::aws::InstanceInfo;
+use crate::aws::run_command::{get_skip_reason, CommandInvocation};
+use crate::cache::{format_age, Cache, CacheEntry};
+use crate::components::command_results::CommandResults;
 use crate::components::config_panel::ConfigPanel;
+use crate::components::confirm_dialog::ConfirmDialog;
+use crate::components::database_list::DatabaseList;
+use crate::aws::profiles::{get_effective_profile, load_profiles};
 use crate::components::instance_details::InstanceDetails;
+use crate::components::profile_list::ProfileList;
 use crate::components::region_list::RegionList;
+use crate::components::tunnel_list::TunnelList;
+use crate::components::status_bar::{Level, StatusBar};
 use crate::components::{Action, HandleAction, Render};
+use crate::session::{is_tmux_active, Session};
 
 use crate::components::instance_selection::InstanceSelection;
+use crate::ui::{resume_terminal, suspend_terminal};
 
 use aws_config::Region;
-use crossterm::event::{self};
+use crossterm::event::{self, Event, KeyCode};
 
 use ratatui::style::Style;
 use ratatui::{prelude::*, widgets::*};
 
 use std::io::Stdout;
+use std::ops::ControlFlow;
+use std::process::{Command, ExitStatus};
 use std::sync::Arc;
 use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::error::TryRecvError;
+use tokio::task::JoinHandle;
 
 use anyhow::Result;
 use thiserror::Error;
 
+mod command_run;
 pub mod config;
+mod loading;
+mod tunnels;
+use command_run::CommandRun;
+use config::Tunnel;
+use loading::InstanceLoad;
+use tunnels::{get_tunnel, BastionLookup, DatabaseDiscovery};
+
+const WAIT_POLL_INTERVAL: Duration = Duration::from_secs(5);
+/// Redraws at least this often, for the loading spinner
+const TICK_RATE: Duration = Duration::from_millis(100);
 
 #[derive(Debug, Clone)]
 pub enum AppStatus {
+    ProfileSelectState,
     RegionSelectState,
     MainScreen,
     ConfigPanelState,
+    TunnelSelectState,
+    DatabaseSelectState,
+    CommandResultsState,
+}
+
+/// Regions whose instances are shown on the main screen
+#[derive(Debug, Clone)]
+enum RegionView {
+    Region(String),
+    AllRegions,
+}
+
+/// What to do once the user confirms the dialog
+#[derive(Debug)]
+enum Prompt {
+    SsoLogin(RegionView),
+    WaitAndConnect(Box<InstanceInfo>),
+    ReplaceBastion(Box<Session>),
 }
 
 #[derive(Error, Debug)]
 pub enum RuntimeError {
     #[error("User exited the application")]
     UserExit,
+    #[error("No running instance {0} to open the tunnel through")]
+    BastionNotFound(String),
 }
 
 #[derive(Debug)]
 pub struct App {
+    provider: Arc<dyn InventoryProvider>,
     config: Arc<Mutex<config::Config>>,
+    profile: Option<String>,
     config_panel: ConfigPanel,
+    profile_select_component: ProfileList,
     region_select_component: RegionList,
+    tunnel_select_component: TunnelList,
+    database_select_component: DatabaseList,
+    command_results_component: CommandResults,
     status: AppStatus,
     info_panel_component: InstanceDetails,
     instance_selection_component: InstanceSelection,
+    prompt: Option<(ConfirmDialog, Prompt)>,
+    waiting: Option<ConfirmDialog>,
+    region_discovery: Option<JoinHandle<Result<Vec<RegionStatus>>>>,
+    database_discovery: Option<DatabaseDiscovery>,
+    bastion_lookup: Option<BastionLookup>,
+    loading: Option<InstanceLoad>,
+    command_run: Option<CommandRun>,
+    /// Reloaded on refresh
+    current_view: Option<RegionView>,
+    no_cache: bool,
+    status_bar: StatusBar,
 }
 
 impl App {
-    pub fn new() -> Result<Self> {
+    pub fn new(
+        provider: Arc<dyn InventoryProvider>,
+        profile: Option<String>,
+        no_cache: bool,
+    ) -> Result<Self> {
         let config = config::Config::new()?;
         let config = Arc::new(Mutex::new(config));
         let config_panel = ConfigPanel::new(config.clone());
         let unlocked = config.lock().unwrap();
         let mut region_select = RegionList::with_items(unlocked.get_visible_regions());
         region_select.set_favorites(unlocked.get_favorite_regions());
+        region_select.set_disabled(unlocked.get_disabled_regions());
         drop(unlocked);
-        Ok(App {
+        // A malformed AWS config shouldn't prevent using the environment's credentials
+        let profile_select = ProfileList::with_items(load_profiles().unwrap_or_default());
+        // Only ask for a profile when there is a choice to make
+        let status = if profile.is_some() || profile_select.is_empty() {
+            AppStatus::RegionSelectState
+        } else {
+            AppStatus::ProfileSelectState
+        };
+        let mut app = App {
+            provider,
             config,
+            profile,
             config_panel,
+            profile_select_component: profile_select,
             region_select_component: region_select,
-            status: AppStatus::RegionSelectState,
+            tunnel_select_component: TunnelList::default(),
+            database_select_component: DatabaseList::default(),
+            command_results_component: CommandResults::default(),
+            status,
             info_panel_component: InstanceDetails::default(),
             instance_selection_component: InstanceSelection::default(),
-        })
+            prompt: None,
+            waiting: None,
+            region_discovery: None,
+            database_discovery: None,
+            bastion_lookup: None,
+            loading: None,
+            command_run: None,
+            current_view: None,
+            no_cache,
+            status_bar: StatusBar::default(),
+        };
+        app.discover_regions();
+        app.update_tunnels();
+        Ok(app)
+    }
+
+    fn update_tunnels(&mut self) {
+        let tunnels = self.config.lock().unwrap().get_tunnels();
+        let mut known_hosts: Vec<String> = Vec::new();
+        for host in tunnels.iter().filter_map(|tunnel| tunnel.remote_host.clone()) {
+            if !known_hosts.contains(&host) {
+                known_hosts.push(host);
+            }
+        }
+        self.instance_selection_component
+            .set_known_hosts(known_hosts);
+        self.tunnel_select_component.update_items(tunnels);
+    }
+
+    fn remember_tunnel(&mut self, session: &Session) -> Result<()> {
+        if let Some(tunnel) = get_tunnel(session) {
+            self.config.lock().unwrap().save_tunnel(tunnel)?;
+            self.update_tunnels();
+        }
+        Ok(())
+    }
+
+    fn find_bastion(&mut self, tunnel: Tunnel) {
+        self.cancel_bastion_lookup();
+        self.status_bar.push(
+            Level::Info,
+            format!("Looking for {} in {}", tunnel.instance_name, tunnel.region),
+        );
+        self.bastion_lookup = Some(BastionLookup::start(&self.provider, tunnel));
+    }
+
+    fn cancel_bastion_lookup(&mut self) {
+        if let Some(lookup) = self.bastion_lookup.take() {
+            lookup.cancel();
+        }
+    }
+
+    /// A replacement for the saved instance is only used once the user confirms it
+    async fn apply_bastion_lookup(&mut self) -> Result<Option<Session>> {
+        let Some(lookup) = self.bastion_lookup.take_if(|l| l.is_finished()) else {
+            return Ok(None);
+        };
+        let (session, question) = lookup.finish().await?;
+        if let Some(question) = question {
+            let dialog = ConfirmDialog::new(" Replace instance ".to_string(), question);
+            self.prompt = Some((dialog, Prompt::ReplaceBastion(Box::new(session))));
+            return Ok(None);
+        }
+        self.remember_tunnel(&session)?;
+        Ok(Some(session))
+    }
+
+    fn discover_regions(&mut self) {
+        if let Some(discovery) = self.region_discovery.take() {
+            discovery.abort();
+        }
+        let provider = self.provider.clone();
+        let profile = self.profile.clone();
+        self.region_discovery = Some(tokio::spawn(async move {
+            provider.fetch_regions(profile).await
+        }));
+    }
+
+    fn select_profile(&mut self, profile: String) {
+        if let Some(region) = self
+            .profile_select_component
+            .current()
+            .and_then(|profile| profile.get_region())
+        {
+            self.region_select_component.select_item(&region);
+        }
+        self.profile = Some(profile);
+        self.status = AppStatus::RegionSelectState;
+        // Opt-in regions are enabled per account
+        self.discover_regions();
+    }
+
+    /// Failures are ignored, the known regions are still usable
+    async fn apply_region_discovery(&mut self) -> Result<()> {
+        let Some(discovery) = self.region_discovery.take_if(|d| d.is_finished()) else {
+            return Ok(());
+        };
+        if let Ok(Ok(regions)) = discovery.await {
+            // The config keeps the regions of the account
+            if !self.provider.is_live() {
+                self.region_select_component
+                    .update_items(regions.into_iter().map(|region| region.name).collect());
+                return Ok(());
+            }
+            let mut config = self.config.lock().unwrap();
+            config.merge_regions(regions)?;
+            self.region_select_component
+                .update_items(config.get_visible_regions());
+            self.region_select_component
+                .set_favorites(config.get_favorite_regions());
+            self.region_select_component
+                .set_disabled(config.get_disabled_regions());
+        }
+        Ok(())
+    }
+
+    fn open_databases(&mut self, region: Region) {
+        self.cancel_database_discovery();
+        self.database_select_component
+            .start_loading(region.to_string());
+        self.status = AppStatus::DatabaseSelectState;
+        self.database_discovery = Some(DatabaseDiscovery::start(
+            &self.provider,
+            region,
+            self.profile.clone(),
+        ));
+    }
+
+    fn cancel_database_discovery(&mut self) {
+        if let Some(discovery) = self.database_discovery.take() {
+            discovery.cancel();
+        }
+    }
+
+    /// Bastions are picked among the instances already fetched
+    async fn apply_database_discovery(&mut self) -> Result<()> {
+        let Some(discovery) = self.database_discovery.take_if(|d| d.is_finished()) else {
+            return Ok(());
+        };
+        match discovery.finish().await {
+            Ok(databases) => {
+                self.database_select_component
+                    .set_items(databases, self.instance_selection_component.get_instances());
+                Ok(())
+            }
+            Err(e) => {
+                self.database_select_component.cancel_loading();
+                Err(e)
+            }
+        }
+    }
+
+    pub fn open_region(&mut self, region: String) {
+        self.open_view(RegionView::Region(region));
+    }
+
+    /// Shows the cached instances right away, then fetches them again if they are stale
+    fn open_view(&mut self, view: RegionView) {
+        self.cancel_loading();
+        if self.no_cache {
+            return self.load_instances(view, false);
+        }
+        let regions = self.get_regions(&view);
+        let entries: Vec<CacheEntry> = regions
+            .iter()
+            .filter_map(|region| Cache::read(&self.profile, region))
+            .collect();
+        if entries.is_empty() {
+            return self.load_instances(view, false);
+        }
+        let complete = entries.len() == regions.len();
+        let age = entries.iter().map(|entry| entry.get_age()).max().unwrap_or_default();
+        let stale = !complete || age >= self.config.lock().unwrap().get_cache_ttl();
+        let instances = entries
+            .into_iter()
+            .flat_map(|entry| entry.into_instances(self.profile.clone()))
+            .collect();
+        let note = match stale {
+            true => format!("stale, cached {} ago", format_age(age)),
+            false => format!("cached {} ago", format_age(age)),
+        };
+        self.status = AppStatus::MainScreen;
+        self.instance_selection_component.show_cached(
+            instances,
+            matches!(view, RegionView::AllRegions),
+            note,
+        );
+        if stale {
+            self.load_instances(view, true);
+        } else {
+            self.current_view = Some(view);
+        }
+    }
+
+    /// "All regions" leaves out the hidden and disabled ones
+    fn get_regions(&self, view: &RegionView) -> Vec<String> {
+        match view {
+            RegionView::Region(region) => vec![region.clone()],
+            RegionView::AllRegions => {
+                let config = self.config.lock().unwrap();
+                let disabled = config.get_disabled_regions();
+                config
+                    .get_visible_regions()
+                    .into_iter()
+                    .filter(|region| !disabled.contains(region))
+                    .collect()
+            }
+        }
+    }
+
+    /// A refresh keeps the current list until the new one is complete
+    fn load_instances(&mut self, view: RegionView, refresh: bool) {
+        self.cancel_loading();
+        let regions = self.get_regions(&view);
+        self.status = AppStatus::MainScreen;
+        self.instance_selection_component.start_loading(
+            regions.len(),
+            matches!(view, RegionView::AllRegions),
+            refresh,
+        );
+        self.current_view = Some(view.clone());
+        self.loading = Some(InstanceLoad::start(
+            &self.provider,
+            &self.profile,
+            view,
+            &regions,
+        ));
+    }
+
+    /// In the "All regions" view failed regions are listed instead of aborting the view
+    fn poll_loading(&mut self) -> Result<()> {
+        let Some(mut load) = self.loading.take() else {
+            return Ok(());
+        };
+        loop {
+            let (region, result) = match load.try_recv() {
+                Ok(received) => received,
+                Err(TryRecvError::Empty) => {
+                    self.loading = Some(load);
+                    return Ok(());
+                }
+                // Every task is done
+                Err(TryRecvError::Disconnected) => break,
+            };
+            let expired_sso = matches!(
+                result.as_ref().map_err(|e| e.downcast_ref()),
+                Err(Some(AwsError::ExpiredSso))
+            );
+            if expired_sso {
+                load.expired_sso += 1;
+            }
+            if let Some(error) = result
+                .as_ref()
+                .ok()
+                .and_then(|instances| instances.iter().find_map(|i| i.get_ssm_error()))
+            {
+                self.status_bar.push(
+                    Level::Warning,
+                    format!("Session Manager status unknown in {}: {}", region, error),
+                );
+            }
+            if let Ok(instances) = &result
+                && !self.no_cache
+                && let Err(e) = Cache::save(&self.profile, &region, instances)
+            {
+                self.status_bar
+                    .push(Level::Warning, format!("Could not cache {}: {:#}", region, e));
+            }
+            match (&load.view, result) {
+                (RegionView::Region(_), Err(e)) => {
+                    self.instance_selection_component.cancel_loading();
+                    if !expired_sso {
+                        return Err(e);
+                    }
+                    self.status = AppStatus::RegionSelectState;
+                    self.prompt_sso_login(load.view);
+                    return Ok(());
+                }
+                (_, result) => self
+                    .instance_selection_component
+                    .add_region_result(region, result),
+            }
+        }
+        self.instance_selection_component.finish_loading();
+        if load.expired_sso > 0 && load.expired_sso == load.get_region_count() {
+            self.status = AppStatus::RegionSelectState;
+            self.prompt_sso_login(load.view);
+        }
+        Ok(())
+    }
+
+    /// Keeps the instances already shown
+    fn cancel_loading(&mut self) {
+        if let Some(load) = self.loading.take() {
+            load.cancel();
+            self.instance_selection_component.cancel_loading();
+        }
+    }
+
+    /// Instances that can't run the command are skipped, one of them would fail its whole batch
+    fn run_command(&mut self, instances: Vec<InstanceInfo>, command: String) {
+        self.cancel_command();
+        self.command_results_component
+            .start(command.clone(), instances.clone());
+        let count = instances.len();
+        let mut runnable = Vec::new();
+        for instance in instances {
+            match get_skip_reason(&instance) {
+                Some(reason) => self
+                    .command_results_component
+                    .update(&instance.get_instance_id(), Ok(CommandInvocation::skipped(reason))),
+                None => runnable.push(instance),
+            }
+        }
+        let skipped = count - runnable.len();
+        if skipped > 0 {
+            self.status_bar.push(
+                Level::Warning,
+                format!("Skipped {} instances not running or offline in Session Manager", skipped),
+            );
+        }
+        self.command_run = Some(CommandRun::start(&self.provider, runnable, &command));
+        self.status = AppStatus::CommandResultsState;
+    }
+
+    fn poll_command(&mut self) {
+        let Some(run) = &mut self.command_run else {
+            return;
+        };
+        loop {
+            match run.try_recv() {
+                Ok((instance_id, result)) => self
+                    .command_results_component
+                    .update(&instance_id, result),
+                Err(TryRecvError::Empty) => return,
+                Err(TryRecvError::Disconnected) => break,
+            }
+        }
+        self.command_run = None;
+    }
+
+    fn cancel_command(&mut self) {
+        if let Some(run) = self.command_run.take() {
+            run.cancel();
+        }
+    }
+
+    fn prompt_sso_login(&mut self, view: RegionView) {
+        let message = format!(
+            "The SSO session for profile '{}' has expired. Run `aws sso login` now?",
+            get_effective_profile(&self.profile)
+        );
+        let dialog = ConfirmDialog::new("SSO login".to_string(), message);
+        self.prompt = Some((dialog, Prompt::SsoLogin(view)));
+    }
+
+    async fn change_instance_state(
+        &mut self,
+        instance: InstanceInfo,
+        change: InstanceStateChange,
+    ) -> Result<()> {
+        self.provider
+            .change_instance_state(&instance, change)
+            .await?;
+        let verb: String = change.into();
+        self.status_bar.push(
+            Level::Info,
+            format!("{} requested for {}", verb, instance.get_name()),
+        );
+        if change == InstanceStateChange::Start {
+            let message = format!(
+                "Wait for {} to be running and online in Session Manager, then connect?",
+                instance.get_name()
+            );
+            let dialog = ConfirmDialog::new(" Starting instance ".to_string(), message);
+            self.prompt = Some((dialog, Prompt::WaitAndConnect(Box::new(instance.clone()))));
+        }
+        if let Some(updated) = self.provider.fetch_instance(&instance).await? {
+            self.instance_selection_component
+                .replace_instance(updated);
+        }
+        Ok(())
+    }
+
+    /// `None` if the user gave up waiting or the instance was terminated
+    async fn wait_until_online(
+        &mut self,
+        terminal: &mut Terminal<CrosstermBackend<Stdout>>,
+        instance: InstanceInfo,
+    ) -> Result<Option<InstanceInfo>> {
+        let mut dialog = ConfirmDialog::new(" Waiting ".to_string(), String::default())
+            .with_footer("'Esc' Stop waiting".to_string());
+        loop {
+            let instance = match self.provider.fetch_instance(&instance).await? {
+                Some(updated)
+                    if !matches!(updated.get_state().as_str(), "shutting-down" | "terminated") =>
+                {
+                    updated
+                }
+                _ => {
+                    self.status_bar.push(
+                        Level::Warning,
+                        format!("{} was terminated while waiting for it", instance.get_name()),
+                    );
+                    return Ok(None);
+                }
+            };
+            self.instance_selection_component
+                .replace_instance(instance.clone());
+            // When the status can't be fetched, trying to connect is the only way to know
+            if instance.is_running() && (instance.is_ssm_online() || instance.is_ssm_unknown()) {
+                return Ok(Some(instance));
+            }
+            dialog.set_message(format!(
+                "Waiting for {} to come online (state: {}, SSM: {})",
+                instance.get_name(),
+                instance.get_state(),
+                instance.get_ssm_status()
+            ));
+            self.waiting = Some(dialog.clone());
+            self.draw(terminal)?;
+            let poll_until = Instant::now() + WAIT_POLL_INTERVAL;
+            while Instant::now() < poll_until {
+                if event::poll(Duration::from_millis(100))?
+                    && let Event::Key(key) = event::read()?
+                    && key.code == KeyCode::Esc
+                {
+                    return Ok(None);
+                }
+            }
+        }
+    }
+
+    fn sso_login(&self, terminal: &mut Terminal<CrosstermBackend<Stdout>>) -> Result<()> {
+        suspend_terminal(terminal)?;
+        let mut command = Command::new("aws");
+        command.args(["sso", "login"]);
+        if let Some(profile) = &self.profile {
+            command.args(["--profile", profile]);
+        }
+        let status = command.status();
+        resume_terminal(terminal)?;
+        status?;
+        Ok(())
     }
 
     pub async fn run(
         &mut self,
         terminal: &mut Terminal<CrosstermBackend<Stdout>>,
-    ) -> Result<InstanceInfo> {
-        let mut should_exit = false;
-        let mut return_value: Option<InstanceInfo> = None;
+    ) -> Result<Session> {
         loop {
-            // render
-            terminal
-                .draw(|frame| {
-                    // Set global layout
-                    let render_area = self.get_component_render_area(frame);
-
-                    match self.status {
-                        AppStatus::RegionSelectState => {
-                            self.region_select_component.render(frame, render_area);
-                        }
-                        AppStatus::MainScreen => {
-                            self.instance_selection_component.render(frame, render_area);
-                        }
-                        AppStatus::ConfigPanelState => {
-                            self.config_panel.render(frame, render_area);
-                        }
-                    }
-                })?;
+            match self.update().await {
+                Ok(Some(session)) => return Ok(session),
+                Ok(None) => {}
+                Err(e) => self.notify_error(e),
+            }
+            self.draw(terminal)?;
 
-            // handle events
+            if !event::poll(TICK_RATE)? {
+                continue;
+            }
             let event = event::read()?;
-            match self.status {
-                AppStatus::RegionSelectState => {
-                    let action = self.region_select_component.handle_action(event)?;
-                    match action {
-                        Action::Exit => {
-                            should_exit = true;
-                        }
-                        Action::Return(region) => {
-                            self.status = AppStatus::MainScreen;
-                            let instances = fetch_instances(Region::new(region)).await?;
-                            self.instance_selection_component
-                                .update_instances(instances);
-                        }
-                        Action::Hide(region) => {
-                            let mut config = self.config.lock().unwrap();
-                            config.hide_region(region)?;
-                            self.region_select_component
-                                .update_items(config.get_visible_regions());
-                        }
-                        Action::Reset => {
-                            let mut config = self.config.lock().unwrap();
-                            config.reset_hidden_regions()?;
-                            self.region_select_component
-                                .update_items(config.get_visible_regions());
-                        }
-                        Action::ToggleFavorite(region) => {
-                            let mut config = self.config.lock().unwrap();
-                            config.toggle_favorite_region(region)?;
-                            self.region_select_component
-                                .set_favorites(config.get_favorite_regions());
-                        }
-                        Action::OpenConfig => {
-                            self.status = AppStatus::ConfigPanelState;
+            match self.handle_event(terminal, event).await {
+                Ok(ControlFlow::Continue(())) => {}
+                Ok(ControlFlow::Break(Some(session))) => return Ok(session),
+                Ok(ControlFlow::Break(None)) => return Err(RuntimeError::UserExit.into()),
+                // Failures are shown in the status bar, the user can retry from there
+                Err(e) => self.notify_error(e),
+            }
+        }
+    }
+
+    /// Applies what the background tasks brought in since the last tick
+    async fn update(&mut self) -> Result<Option<Session>> {
+        self.apply_region_discovery().await?;
+        self.apply_database_discovery().await?;
+        self.poll_loading()?;
+        self.poll_command();
+        self.instance_selection_component.tick();
+        self.apply_bastion_lookup().await
+    }
+
+    pub fn get_config(&self) -> config::Config {
+        self.config.lock().unwrap().clone()
+    }
+
+    pub fn get_stay_open(&self) -> bool {
+        self.config.lock().unwrap().get_stay_open()
+    }
+
+    pub fn session_ended(&mut self, label: String, result: Result<Option<ExitStatus>>) {
+        match result {
+            Ok(None) => self
+                .status_bar
+                .push(Level::Info, format!("Opened {} in tmux", label)),
+            Ok(Some(status)) if status.success() => self
+                .status_bar
+                .push(Level::Info, format!("Session to {} ended", label)),
+            Ok(Some(status)) => self.status_bar.push(
+                Level::Warning,
+                format!("Session to {} ended with {}", label, status),
+            ),
+            Err(e) => self.notify_error(e.context(format!("Session to {} failed", label))),
+        }
+    }
+
+    fn notify_error(&mut self, error: anyhow::Error) {
+        self.status_bar.push(Level::Error, format!("{:#}", error));
+    }
+
+    /// Breaks with the session to start, or `None` when the user quits
+    async fn handle_event(
+        &mut self,
+        terminal: &mut Terminal<CrosstermBackend<Stdout>>,
+        event: Event,
+    ) -> Result<ControlFlow<Option<Session>>> {
+        if let Some((dialog, _)) = &mut self.prompt {
+            match dialog.handle_action(event)? {
+                Action::Confirm => match self.prompt.take().map(|(_, prompt)| prompt) {
+                    Some(Prompt::SsoLogin(view)) => {
+                        self.sso_login(terminal)?;
+                        self.load_instances(view, false);
+                    }
+                    Some(Prompt::ReplaceBastion(session)) => {
+                        self.remember_tunnel(&session)?;
+                        return Ok(ControlFlow::Break(Some(*session)));
+                    }
+                    Some(Prompt::WaitAndConnect(instance)) => {
+                        let online = self.wait_until_online(terminal, *instance).await;
+                        // Also when polling failed, the popup must not outlive the wait
+                        self.waiting = None;
+                        if let Some(instance) = online? {
+                            return Ok(ControlFlow::Break(Some(Session::Shell(instance))));
                         }
-                        _ => {}
                     }
+                    None => {}
+                },
+                Action::Exit => {
+                    self.prompt = None;
                 }
-                AppStatus::MainScreen => {
-                    let action = self.instance_selection_component.handle_action(event)?;
-                    match action {
-                        Action::Exit => {
-                            self.status = AppStatus::RegionSelectState;
-                        }
-                        Action::ReturnInstance(instance) => {
-                            should_exit = true;
-                            return_value = Some(instance);
+                _ => {}
+            }
+            return Ok(ControlFlow::Continue(()));
+        }
+        match self.status {
+            AppStatus::ProfileSelectState => {
+                let action = self.profile_select_component.handle_action(event)?;
+                match action {
+                    Action::Exit => {
+                        return Ok(ControlFlow::Break(None));
+                    }
+                    Action::Return(profile) => {
+                        self.select_profile(profile);
+                    }
+                    _ => {}
+                }
+            }
+            AppStatus::RegionSelectState => {
+                let action = self.region_select_component.handle_action(event)?;
+                match action {
+                    Action::Exit => {
+                        if self.profile_select_component.is_empty() {
+                            return Ok(ControlFlow::Break(None));
                         }
-                        Action::Select(instance) => {
-                            self.info_panel_component.set_instance(instance);
+                        self.status = AppStatus::ProfileSelectState;
+                    }
+                    Action::Return(region) => {
+                        self.open_region(region);
+                    }
+                    Action::OpenAllRegions => {
+                        self.open_view(RegionView::AllRegions);
+                    }
+                    Action::Hide(region) => {
+                        let mut config = self.config.lock().unwrap();
+                        config.hide_region(region)?;
+                        self.region_select_component
+                            .update_items(config.get_visible_regions());
+                    }
+                    Action::Reset => {
+                        let mut config = self.config.lock().unwrap();
+                        config.reset_hidden_regions()?;
+                        self.region_select_component
+                            .update_items(config.get_visible_regions());
+                    }
+                    Action::ToggleFavorite(region) => {
+                        let mut config = self.config.lock().unwrap();
+                        config.toggle_favorite_region(region)?;
+                        self.region_select_component
+                            .set_favorites(config.get_favorite_regions());
+                    }
+                    Action::OpenConfig => {
+                        self.status = AppStatus::ConfigPanelState;
+                    }
+                    Action::OpenTunnels => {
+                        self.status = AppStatus::TunnelSelectState;
+                    }
+                    _ => {}
+                }
+            }
+            AppStatus::MainScreen => {
+                let action = self.instance_selection_component.handle_action(event)?;
+                match action {
+                    Action::Exit => {
+                        self.cancel_loading();
+                        self.status = AppStatus::RegionSelectState;
+                    }
+                    Action::Cancel => {
+                        self.cancel_loading();
+                    }
+                    Action::Refresh => {
+                        if let Some(view) = self.current_view.clone() {
+                            self.load_instances(view, true);
                         }
-                        _ => {}
                     }
+                    Action::ReturnInstance(instance) => {
+                        return Ok(ControlFlow::Break(Some(Session::Shell(instance))));
+                    }
+                    Action::OpenSessionDocument(instance) => {
+                        let default = self.config.lock().unwrap().get_document_rule(&instance);
+                        self.instance_selection_component
+                            .open_session_document(instance, default);
+                    }
+                    Action::SaveSessionDocument(target, document) => {
+                        let targets = match target.contains('=') {
+                            true => format!("instances tagged {}", target),
+                            false => target.clone(),
+                        };
+                        let message = match &document {
+                            Some(document) => {
+                                format!("Shells to {} now start {}", targets, document.name)
+                            }
+                            None => format!("Shells to {} now start the default shell", targets),
+                        };
+                        self.config
+                            .lock()
+                            .unwrap()
+                            .set_session_document(target, document)?;
+                        self.status_bar.push(Level::Info, message);
+                    }
+                    Action::StartSession(Session::Shells(_))
+                        if !is_tmux_active(self.config.lock().unwrap().get_tmux_layout()) =>
+                    {
+                        self.status_bar.push(
+                            Level::Warning,
+                            "Opening several sessions at once needs tmux and a tmux layout set \
+                             in the config panel"
+                                .to_string(),
+                        );
+                    }
+                    Action::StartSession(session) => {
+                        self.remember_tunnel(&session)?;
+                        return Ok(ControlFlow::Break(Some(session)));
+                    }
+                    Action::Select(instance) => {
+                        self.info_panel_component.set_instance(instance);
+                    }
+                    Action::ChangeInstanceState(instance, change) => {
+                        self.change_instance_state(instance, change).await?;
+                    }
+                    Action::OpenDatabases(region) => {
+                        self.open_databases(region);
+                    }
+                    Action::RunCommand(instances, command) => {
+                        self.run_command(instances, command);
+                    }
+                    _ => {}
                 }
-                AppStatus::ConfigPanelState => {
-                    let action = self.config_panel.handle_action(event)?;
-                    if let Action::Exit = action {
+            }
+            AppStatus::CommandResultsState => {
+                if let Action::Exit = self.command_results_component.handle_action(event)? {
+                    self.cancel_command();
+                    self.status = AppStatus::MainScreen;
+                }
+            }
+            AppStatus::DatabaseSelectState => {
+                let action = self.database_select_component.handle_action(event)?;
+                match action {
+                    Action::Exit => {
+                        self.cancel_database_discovery();
+                        self.status = AppStatus::MainScreen;
+                    }
+                    Action::StartSession(session) => {
+                        self.remember_tunnel(&session)?;
+                        return Ok(ControlFlow::Break(Some(session)));
+                    }
+                    Action::Notify(level, message) => {
+                        self.status_bar.push(level, message);
+                    }
+                    _ => {}
+                }
+            }
+            AppStatus::TunnelSelectState => {
+                let action = self.tunnel_select_component.handle_action(event)?;
+                match action {
+                    Action::Exit => {
+                        self.cancel_bastion_lookup();
                         self.status = AppStatus::RegionSelectState;
                     }
+                    Action::LaunchTunnel(tunnel) => {
+                        self.find_bastion(tunnel);
+                    }
+                    Action::DeleteTunnel(tunnel) => {
+                        self.config.lock().unwrap().remove_tunnel(&tunnel)?;
+                        self.update_tunnels();
+                    }
+                    _ => {}
                 }
             }
-
-            if should_exit {
-                break;
+            AppStatus::ConfigPanelState => {
+                let action = self.config_panel.handle_action(event)?;
+                match action {
+                    Action::Exit => {
+                        self.status = AppStatus::RegionSelectState;
+                    }
+                    Action::Notify(level, message) => {
+                        self.status_bar.push(level, message);
+                    }
+                    _ => {}
+                }
             }
         }
-        match return_value {
-            Some(instance) => Ok(instance),
-            None => Err(RuntimeError::UserExit.into()),
-        }
+        Ok(ControlFlow::Continue(()))
+    }
+
+    fn draw(&mut self, terminal: &mut Terminal<CrosstermBackend<Stdout>>) -> Result<()> {
+        terminal.draw(|frame| {
+            // Set global layout
+            let render_area = self.get_component_render_area(frame);
+
+            match self.status {
+                AppStatus::ProfileSelectState => {
+                    self.profile_select_component.render(frame, render_area);
+                }
+                AppStatus::RegionSelectState => {
+                    self.region_select_component.render(frame, render_area);
+                }
+                AppStatus::MainScreen => {
+                    self.instance_selection_component.render(frame, render_area);
+                }
+                AppStatus::ConfigPanelState => {
+                    self.config_panel.render(frame, render_area);
+                }
+                AppStatus::TunnelSelectState => {
+                    self.tunnel_select_component.render(frame, render_area);
+                }
+                AppStatus::DatabaseSelectState => {
+                    self.database_select_component.render(frame, render_area);
+                }
+                AppStatus::CommandResultsState => {
+                    self.command_results_component.render(frame, render_area);
+                }
+            }
+            if let Some((dialog, _)) = &mut self.prompt {
+                dialog.render(frame, render_area);
+            }
+            if let Some(dialog) = &mut self.waiting {
+                dialog.render(frame, render_area);
+            }
+        })?;
+        Ok(())
     }
 
     /**
-     * Creates the app layout and returns the area for components to render themselves
+     * Creates the app layout, with the tabs and the status bar, and returns the area for
+     * components to render themselves
      */
-    fn get_component_render_area(&self, frame: &mut Frame) -> Rect {
+    fn get_component_render_area(&mut self, frame: &mut Frame) -> Rect {
         let outer = Layout::default()
             .direction(Direction::Vertical)
             .margin(0)
-            .constraints([Constraint::Max(3), Constraint::Fill(1)].as_ref())
+            .constraints(
+                [
+                    Constraint::Max(3),
+                    Constraint::Fill(1),
+                    Constraint::Length(1),
+                ]
+                .as_ref(),
+            )
             .split(frame.area());
+        self.status_bar.render(frame, outer[2]);
 
-        let tabs = Tabs::new(vec!["Region", "Instances", "Connection"])
+        let tabs = Tabs::new(vec!["Profile", "Region", "Instances", "Connection"])
             .block(Block::bordered())
             .style(Style::default().white())
             .highlight_style(Style::default().yellow())
             .select(match self.status {
-                AppStatus::RegionSelectState => Some(0),
-                AppStatus::MainScreen => Some(1),
+                AppStatus::ProfileSelectState => Some(0),
+                AppStatus::RegionSelectState => Some(1),
+                AppStatus::MainScreen
+                | AppStatus::DatabaseSelectState
+                | AppStatus::CommandResultsState => Some(2),
                 _ => None,
             });
         frame.render_widget(tabs, outer[0]);
@@ -0,0 +1,148 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use tokio::sync::mpsc::{self, error::TryRecvError};
+use tokio::task::JoinHandle;
+
+use crate::aws::run_command::{get_script_document, CommandInvocation};
+use crate::aws::{InstanceInfo, InventoryProvider};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// Polls failing in a row before the instances still pending are reported as errors
+const POLL_RETRIES: u32 = 5;
+const DEADLINE: Duration = Duration::from_secs(10 * 60);
+/// Most instances SendCommand accepts at once
+const BATCH_SIZE: usize = 50;
+
+type Update = (String, Result<CommandInvocation>);
+
+/// Command sent with Run Command, one task per batch of instances polling their invocations
+#[derive(Debug)]
+pub struct CommandRun {
+    receiver: mpsc::UnboundedReceiver<Update>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl CommandRun {
+    pub fn start(
+        provider: &Arc<dyn InventoryProvider>,
+        instances: Vec<InstanceInfo>,
+        command: &str,
+    ) -> CommandRun {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let tasks = get_batches(instances)
+            .into_iter()
+            .map(|batch| {
+                let sender = sender.clone();
+                let provider = provider.clone();
+                let command = command.to_string();
+                tokio::spawn(async move { run_batch(provider, batch, command, sender).await })
+            })
+            .collect();
+        CommandRun { receiver, tasks }
+    }
+
+    /// The next invocation polled, `Disconnected` once every one is finished
+    pub fn try_recv(&mut self) -> Result<Update, TryRecvError> {
+        self.receiver.try_recv()
+    }
+
+    /// The commands keep running on the instances
+    pub fn cancel(self) {
+        for task in self.tasks {
+            task.abort();
+        }
+    }
+}
+
+/// Groups the instances SendCommand can take together: same region and script document
+fn get_batches(instances: Vec<InstanceInfo>) -> Vec<Vec<InstanceInfo>> {
+    let mut batches: Vec<Vec<InstanceInfo>> = Vec::new();
+    for instance in instances {
+        let batch = batches.iter_mut().find(|batch| {
+            batch.len() < BATCH_SIZE
+                && batch[0].get_region() == instance.get_region()
+                && get_script_document(&batch[0]) == get_script_document(&instance)
+        });
+        match batch {
+            Some(batch) => batch.push(instance),
+            None => batches.push(vec![instance]),
+        }
+    }
+    batches
+}
+
+async fn run_batch(
+    provider: Arc<dyn InventoryProvider>,
+    batch: Vec<InstanceInfo>,
+    command: String,
+    sender: mpsc::UnboundedSender<Update>,
+) {
+    let fail = |instances: &[InstanceInfo], message: String| {
+        for instance in instances {
+            let error = anyhow::anyhow!("{}", message);
+            let _ = sender.send((instance.get_instance_id(), Err(error)));
+        }
+    };
+    let document = get_script_document(&batch[0]);
+    let command_id = match provider.send_command(&batch, document, &command).await {
+        Ok(command_id) => command_id,
+        Err(e) => return fail(&batch, format!("{:#}", e)),
+    };
+    let deadline = Instant::now() + DEADLINE;
+    let mut pending = batch;
+    let mut started = HashSet::new();
+    let mut failures = 0;
+    while !pending.is_empty() {
+        // Backs off while polling fails, e.g. when throttled
+        tokio::time::sleep(POLL_INTERVAL * (failures + 1)).await;
+        if Instant::now() >= deadline {
+            let (running, missing): (Vec<_>, Vec<_>) = pending
+                .into_iter()
+                .partition(|instance| started.contains(&instance.get_instance_id()));
+            let minutes = DEADLINE.as_secs() / 60;
+            fail(&running, format!("Still running after {} minutes, stopped polling", minutes));
+            return fail(&missing, format!("Not picked up by the agent in {} minutes", minutes));
+        }
+        let mut invocations = match provider.get_command_invocations(&pending, &command_id).await
+        {
+            Ok(invocations) => invocations,
+            Err(_) if failures + 1 < POLL_RETRIES => {
+                failures += 1;
+                continue;
+            }
+            Err(e) => return fail(&pending, format!("{:#}", e)),
+        };
+        failures = 0;
+        // Instances missing from the invocations are still pending
+        pending.retain(|instance| {
+            let Some(invocation) = invocations.remove(&instance.get_instance_id()) else {
+                return true;
+            };
+            started.insert(instance.get_instance_id());
+            let finished = invocation.is_finished();
+            let _ = sender.send((instance.get_instance_id(), Ok(invocation)));
+            !finished
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aws::fixture::FixtureInventory;
+
+    #[test]
+    fn batches_by_region_and_size() {
+        let instances = FixtureInventory::test_instances();
+        let sizes = |batches: &[Vec<InstanceInfo>]| -> Vec<usize> {
+            batches.iter().map(|batch| batch.len()).collect()
+        };
+        assert_eq!(sizes(&get_batches(instances.clone())), [3, 2]);
+
+        let many: Vec<InstanceInfo> = (0..BATCH_SIZE + 1).map(|_| instances[0].clone()).collect();
+        assert_eq!(sizes(&get_batches(many)), [BATCH_SIZE, 1]);
+    }
+}
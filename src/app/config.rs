@@ -1,25 +1,195 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use home::home_dir;
 use serde::{Deserialize, Serialize};
 use serde_json::{from_str, to_string_pretty as to_string};
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     io::{Read, Write},
     path::PathBuf,
 };
 
-#[derive(Debug, Default, Serialize, Deserialize)]
+use crate::aws::{InstanceInfo, RegionStatus};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 struct RegionConfig {
     hidden: bool,
     favorite: bool,
+    /// Opt-in region the account hasn't enabled, as last reported by DescribeRegions
+    #[serde(default)]
+    disabled: bool,
+}
+
+/// Port forwarding session remembered to be started again from the tunnel list
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Tunnel {
+    pub instance_id: String,
+    pub instance_name: String,
+    pub region: String,
+    pub profile: Option<String>,
+    /// Host reached through the instance, `None` for a port of the instance itself
+    pub remote_host: Option<String>,
+    pub remote_port: u16,
+    pub local_port: u16,
+}
+
+impl Tunnel {
+    /// Where the tunnel leads, like "db.internal:5432"
+    pub fn get_destination(&self) -> String {
+        format!(
+            "{}:{}",
+            self.remote_host.as_deref().unwrap_or(&self.instance_name),
+            self.remote_port
+        )
+    }
+}
+
+/// SSM document a shell session starts instead of the default one, with its parameters
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionDocument {
+    pub name: String,
+    /// As given to `--parameters`, e.g. {"command": ["sudo -iu app"]}
+    #[serde(default)]
+    pub parameters: BTreeMap<String, Vec<String>>,
+}
+
+impl SessionDocument {
+    /// Parses the parameters as a JSON object, or as a single `key=value` parameter
+    pub fn parse_parameters(parameters: &str) -> Result<BTreeMap<String, Vec<String>>> {
+        let parameters = parameters.trim();
+        if parameters.is_empty() {
+            return Ok(BTreeMap::new());
+        }
+        if parameters.starts_with('{') {
+            let values: BTreeMap<String, serde_json::Value> = from_str(parameters)?;
+            return values
+                .into_iter()
+                .map(|(key, value)| match value {
+                    serde_json::Value::String(value) => Ok((key, vec![value])),
+                    value => Ok((key, serde_json::from_value(value)?)),
+                })
+                .collect();
+        }
+        match parameters.split_once('=') {
+            Some((key, value)) if !key.trim().is_empty() => Ok(BTreeMap::from([(
+                key.trim().to_string(),
+                vec![value.to_string()],
+            )])),
+            _ => Err(anyhow::anyhow!(
+                "Parameters must be key=value or a JSON object"
+            )),
+        }
+    }
+
+    /// The parameters as typed in `parse_parameters`
+    pub fn format_parameters(&self) -> String {
+        match self.parameters.iter().next() {
+            None => String::new(),
+            Some((key, values)) if self.parameters.len() == 1 && values.len() == 1 => {
+                format!("{}={}", key, values[0])
+            }
+            Some(_) => serde_json::to_string(&self.parameters).unwrap_or_default(),
+        }
+    }
+}
+
+/// Document the shells to matching instances start by default
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DocumentRule {
+    /// Instance id, or tag as `Key=Value`
+    pub target: String,
+    pub document: SessionDocument,
+}
+
+impl DocumentRule {
+    /// Checks the target is an instance id or a tag with a key
+    pub fn parse_target(target: &str) -> Result<String> {
+        let target = target.trim();
+        match target.split_once('=') {
+            _ if target.is_empty() => Err(anyhow::anyhow!("The target can't be empty")),
+            Some((key, _)) if key.trim().is_empty() => {
+                Err(anyhow::anyhow!("Tag targets must be Key=Value"))
+            }
+            _ => Ok(target.to_string()),
+        }
+    }
+
+    pub fn matches(&self, instance: &InstanceInfo) -> bool {
+        match self.target.split_once('=') {
+            Some((key, value)) => instance.get_tags().get(key).is_some_and(|tag| tag == value),
+            None => self.target == instance.get_instance_id(),
+        }
+    }
+}
+
+/// How sessions open when sm_connect runs inside tmux
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TmuxLayout {
+    /// In the current terminal, as outside tmux
+    #[default]
+    Off,
+    /// One window per session
+    Windows,
+    /// One tiled pane per session, in a new window
+    Panes,
+    /// Tiled panes receiving the same input
+    SynchronizedPanes,
+}
+
+impl TmuxLayout {
+    pub fn next(self) -> TmuxLayout {
+        match self {
+            TmuxLayout::Off => TmuxLayout::Windows,
+            TmuxLayout::Windows => TmuxLayout::Panes,
+            TmuxLayout::Panes => TmuxLayout::SynchronizedPanes,
+            TmuxLayout::SynchronizedPanes => TmuxLayout::Off,
+        }
+    }
+}
+
+impl From<TmuxLayout> for String {
+    fn from(layout: TmuxLayout) -> String {
+        match layout {
+            TmuxLayout::Windows => "one window per session".to_string(),
+            TmuxLayout::Panes => "tiled panes".to_string(),
+            TmuxLayout::SynchronizedPanes => "synchronized tiled panes".to_string(),
+            TmuxLayout::Off => "off".to_string(),
+        }
+    }
 }
 
 // when it becomes stable as const , switch to Duration::from_days(7).as_secs();
 // https://github.com/rust-lang/rust/issues/120301
 const DEFAULT_RECENT_TIMEOUT: u64 = 60 * 60 * 24 * 7;
-#[derive(Debug, Serialize, Deserialize)]
+const DEFAULT_CACHE_TTL: u64 = 60 * 5;
+
+fn default_cache_ttl() -> u64 {
+    DEFAULT_CACHE_TTL
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
     recent_timeout: u64,
+    /// Seconds during which cached instances are shown without fetching them again
+    #[serde(default = "default_cache_ttl")]
+    cache_ttl: u64,
+    /// Replaces the AWS endpoints, unless `--endpoint-url` or AWS_ENDPOINT_URL is set
+    #[serde(default)]
+    endpoint_url: Option<String>,
+    /// Most recently used first
+    #[serde(default)]
+    tunnels: Vec<Tunnel>,
+    #[serde(default)]
+    tmux_layout: TmuxLayout,
+    /// Come back to the TUI once a session ends, instead of exiting
+    #[serde(default)]
+    stay_open: bool,
+    #[serde(default)]
+    session_documents: Vec<DocumentRule>,
+    /// Replaces `aws ssm start-session`, with {instance_id}, {region}, {profile}, {name}
+    /// and {private_ip} placeholders
+    #[serde(default)]
+    session_command: Option<String>,
     regions: HashMap<String, RegionConfig>,
 }
 
@@ -32,10 +202,18 @@ impl Default for Config {
         Config {
             regions,
             recent_timeout: DEFAULT_RECENT_TIMEOUT,
+            cache_ttl: DEFAULT_CACHE_TTL,
+            endpoint_url: None,
+            tunnels: Vec::new(),
+            tmux_layout: TmuxLayout::default(),
+            stay_open: false,
+            session_documents: Vec::new(),
+            session_command: None,
         }
     }
 }
 
+// Only used until the regions are discovered through DescribeRegions
 const DEFAULT_REGIONS: &[&str] = &[
     "af-south-1",
     "ap-east-1",
@@ -92,6 +270,21 @@ impl Config {
         Ok(config)
     }
 
+    /// Reads the config like `new`, but fails instead of resetting it when it is invalid,
+    /// e.g. after a mistake editing it by hand
+    pub fn load() -> Result<Config> {
+        let config_path = Config::get_config_path()?;
+        let contents = match std::fs::read_to_string(&config_path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e.into()),
+        };
+        if contents.trim().is_empty() {
+            return Ok(Config::default());
+        }
+        from_str(&contents).with_context(|| format!("invalid config {}", config_path.display()))
+    }
+
     pub fn persist(&self) -> Result<()> {
         let config_path = Config::get_config_path()?;
         let mut file = std::fs::OpenOptions::new()
@@ -118,6 +311,23 @@ impl Config {
             .collect()
     }
 
+    pub fn get_disabled_regions(&self) -> Vec<String> {
+        self.regions
+            .iter()
+            .filter(|(_, region)| region.disabled)
+            .map(|(region, _)| region.to_string())
+            .collect()
+    }
+
+    /// Adds newly discovered regions and refreshes which ones are enabled,
+    /// keeping the hidden and favorite flags of the known ones
+    pub fn merge_regions(&mut self, regions: Vec<RegionStatus>) -> Result<()> {
+        for region in regions {
+            self.regions.entry(region.name).or_default().disabled = !region.enabled;
+        }
+        self.persist()
+    }
+
     pub fn hide_region(&mut self, region: String) -> Result<()> {
         if let Some(region) = self.regions.get_mut(&region) {
             region.hidden = true;
@@ -169,4 +379,226 @@ impl Config {
         self.recent_timeout = timeout;
         self.persist()
     }
+
+    pub fn get_endpoint_url(&self) -> Option<String> {
+        self.endpoint_url.clone()
+    }
+
+    pub fn get_tunnels(&self) -> Vec<Tunnel> {
+        self.tunnels.clone()
+    }
+
+    /// Remembers the tunnel, moving it first when it is already known
+    pub fn save_tunnel(&mut self, tunnel: Tunnel) -> Result<()> {
+        self.tunnels.retain(|known| known != &tunnel);
+        self.tunnels.insert(0, tunnel);
+        self.persist()
+    }
+
+    pub fn remove_tunnel(&mut self, tunnel: &Tunnel) -> Result<()> {
+        self.tunnels.retain(|known| known != tunnel);
+        self.persist()
+    }
+
+    pub fn get_cache_ttl(&self) -> u64 {
+        self.cache_ttl
+    }
+
+    pub fn set_cache_ttl(&mut self, ttl: u64) -> Result<()> {
+        self.cache_ttl = ttl;
+        self.persist()
+    }
+
+    pub fn get_tmux_layout(&self) -> TmuxLayout {
+        self.tmux_layout
+    }
+
+    pub fn set_tmux_layout(&mut self, layout: TmuxLayout) -> Result<()> {
+        self.tmux_layout = layout;
+        self.persist()
+    }
+
+    pub fn get_stay_open(&self) -> bool {
+        self.stay_open
+    }
+
+    pub fn toggle_stay_open(&mut self) -> Result<()> {
+        self.stay_open = !self.stay_open;
+        self.persist()
+    }
+
+    pub fn get_session_command(&self) -> Option<String> {
+        self.session_command.clone()
+    }
+
+    /// An empty command goes back to `aws ssm start-session`
+    pub fn set_session_command(&mut self, command: String) -> Result<()> {
+        let command = command.trim().to_string();
+        self.session_command = (!command.is_empty()).then_some(command);
+        self.persist()
+    }
+
+    /// The rule for the instance id, else the first matching tag rule
+    pub fn get_document_rule(&self, instance: &InstanceInfo) -> Option<DocumentRule> {
+        self.session_documents
+            .iter()
+            .find(|rule| rule.target == instance.get_instance_id())
+            .or_else(|| {
+                self.session_documents
+                    .iter()
+                    .find(|rule| rule.matches(instance))
+            })
+            .cloned()
+    }
+
+    pub fn get_session_document(&self, instance: &InstanceInfo) -> Option<SessionDocument> {
+        self.get_document_rule(instance).map(|rule| rule.document)
+    }
+
+    /// Sets the default document of the target, or removes it when `None`
+    pub fn set_session_document(
+        &mut self,
+        target: String,
+        document: Option<SessionDocument>,
+    ) -> Result<()> {
+        self.session_documents.retain(|rule| rule.target != target);
+        if let Some(document) = document {
+            self.session_documents.push(DocumentRule { target, document });
+        }
+        self.persist()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aws::fixture::FixtureInventory;
+
+    fn document(parameters: &str) -> SessionDocument {
+        SessionDocument {
+            name: "AWS-StartInteractiveCommand".to_string(),
+            parameters: SessionDocument::parse_parameters(parameters).unwrap(),
+        }
+    }
+
+    #[test]
+    fn parse_key_value_parameters() {
+        assert_eq!(
+            SessionDocument::parse_parameters(" command=sudo -iu app ").unwrap(),
+            BTreeMap::from([("command".to_string(), vec!["sudo -iu app".to_string()])])
+        );
+        assert!(SessionDocument::parse_parameters("  ").unwrap().is_empty());
+        assert!(SessionDocument::parse_parameters("command").is_err());
+        assert!(SessionDocument::parse_parameters("=value").is_err());
+    }
+
+    #[test]
+    fn parse_json_parameters() {
+        assert_eq!(
+            SessionDocument::parse_parameters(
+                r#"{"portNumber": ["5432"], "host": "db.internal"}"#
+            )
+            .unwrap(),
+            BTreeMap::from([
+                ("host".to_string(), vec!["db.internal".to_string()]),
+                ("portNumber".to_string(), vec!["5432".to_string()]),
+            ])
+        );
+        assert!(SessionDocument::parse_parameters(r#"{"portNumber": 5432}"#).is_err());
+        assert!(SessionDocument::parse_parameters("{").is_err());
+    }
+
+    #[test]
+    fn format_parameters_round_trip() {
+        for parameters in [
+            "",
+            "command=sudo -iu app",
+            r#"{"host":["db.internal"],"portNumber":["5432"]}"#,
+            r#"{"command":["a","b"]}"#,
+        ] {
+            assert_eq!(document(parameters).format_parameters(), parameters);
+        }
+    }
+
+    #[test]
+    fn rules_match_instance_ids_and_tags() {
+        let instances = FixtureInventory::test_instances();
+        let matching = |target: &str| -> Vec<String> {
+            let rule = DocumentRule {
+                target: target.to_string(),
+                document: document(""),
+            };
+            instances
+                .iter()
+                .filter(|instance| rule.matches(instance))
+                .map(|instance| instance.get_name())
+                .collect()
+        };
+        assert_eq!(matching("i-0a1b2c3d4e5f60004"), ["bastion"]);
+        assert_eq!(matching("env=prod"), ["web-1", "web-2", "batch"]);
+        assert_eq!(matching("env=dev"), Vec::<String>::new());
+        assert_eq!(matching("env"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn instance_id_rules_come_first() {
+        let config = Config {
+            session_documents: vec![
+                DocumentRule {
+                    target: "env=prod".to_string(),
+                    document: document("command=bash"),
+                },
+                DocumentRule {
+                    target: "i-0a1b2c3d4e5f60001".to_string(),
+                    document: document("command=sudo -iu app"),
+                },
+            ],
+            ..Default::default()
+        };
+        let instances = FixtureInventory::test_instances();
+        let targets: Vec<Option<String>> = instances[..4]
+            .iter()
+            .map(|instance| config.get_document_rule(instance).map(|rule| rule.target))
+            .collect();
+        assert_eq!(
+            targets,
+            [
+                Some("i-0a1b2c3d4e5f60001".to_string()),
+                Some("env=prod".to_string()),
+                Some("env=prod".to_string()),
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn rule_targets() {
+        assert_eq!(DocumentRule::parse_target(" Role=web ").unwrap(), "Role=web");
+        assert_eq!(
+            DocumentRule::parse_target("i-0a1b2c3d4e5f60001").unwrap(),
+            "i-0a1b2c3d4e5f60001"
+        );
+        assert!(DocumentRule::parse_target("").is_err());
+        assert!(DocumentRule::parse_target("=web").is_err());
+    }
+
+    #[test]
+    fn tmux_layouts_cycle() {
+        let mut layout = TmuxLayout::default();
+        assert_eq!(layout, TmuxLayout::Off);
+        let mut seen = Vec::new();
+        for _ in 0..4 {
+            layout = layout.next();
+            seen.push(layout);
+        }
+        assert_eq!(
+            seen,
+            [
+                TmuxLayout::Windows,
+                TmuxLayout::Panes,
+                TmuxLayout::SynchronizedPanes,
+                TmuxLayout::Off,
+            ]
+        );
+    }
 }
@@ -0,0 +1,65 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use aws_config::Region;
+use tokio::sync::mpsc::{self, error::TryRecvError};
+use tokio::task::JoinHandle;
+
+use super::RegionView;
+use crate::aws::{InstanceInfo, InventoryProvider};
+
+/// Instances being fetched in the background, one task per region
+#[derive(Debug)]
+pub struct InstanceLoad {
+    pub view: RegionView,
+    receiver: mpsc::UnboundedReceiver<(String, Result<Vec<InstanceInfo>>)>,
+    tasks: Vec<JoinHandle<()>>,
+    pub expired_sso: usize,
+}
+
+impl InstanceLoad {
+    pub fn start(
+        provider: &Arc<dyn InventoryProvider>,
+        profile: &Option<String>,
+        view: RegionView,
+        regions: &[String],
+    ) -> InstanceLoad {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let tasks = regions
+            .iter()
+            .cloned()
+            .map(|region| {
+                let sender = sender.clone();
+                let provider = provider.clone();
+                let profile = profile.clone();
+                tokio::spawn(async move {
+                    let result = provider
+                        .fetch_instances(Region::new(region.clone()), profile)
+                        .await;
+                    let _ = sender.send((region, result));
+                })
+            })
+            .collect();
+        InstanceLoad {
+            view,
+            receiver,
+            tasks,
+            expired_sso: 0,
+        }
+    }
+
+    /// The next region fetched, `Disconnected` once every region was
+    pub fn try_recv(&mut self) -> Result<(String, Result<Vec<InstanceInfo>>), TryRecvError> {
+        self.receiver.try_recv()
+    }
+
+    pub fn get_region_count(&self) -> usize {
+        self.tasks.len()
+    }
+
+    pub fn cancel(self) {
+        for task in self.tasks {
+            task.abort();
+        }
+    }
+}
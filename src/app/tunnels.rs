@@ -0,0 +1,127 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use aws_config::Region;
+use tokio::task::JoinHandle;
+
+use super::config::Tunnel;
+use super::RuntimeError;
+use crate::aws::rds::DatabaseInfo;
+use crate::aws::{InstanceInfo, InventoryProvider};
+use crate::session::Session;
+
+/// Running instances of a saved tunnel's region, fetched to find the instance it goes through
+#[derive(Debug)]
+pub struct BastionLookup {
+    tunnel: Tunnel,
+    task: JoinHandle<Result<Vec<InstanceInfo>>>,
+}
+
+impl BastionLookup {
+    pub fn start(provider: &Arc<dyn InventoryProvider>, tunnel: Tunnel) -> BastionLookup {
+        let provider = provider.clone();
+        let region = Region::new(tunnel.region.clone());
+        let profile = tunnel.profile.clone();
+        let task = tokio::spawn(async move { provider.fetch_instances(region, profile).await });
+        BastionLookup { tunnel, task }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.task.is_finished()
+    }
+
+    pub fn cancel(self) {
+        self.task.abort();
+    }
+
+    /// The tunnel through the saved instance, or through a running instance with the same
+    /// name along with the question to ask before using it
+    pub async fn finish(self) -> Result<(Session, Option<String>)> {
+        let tunnel = self.tunnel;
+        let instances: Vec<InstanceInfo> = self
+            .task
+            .await??
+            .into_iter()
+            .filter(|instance| instance.is_running())
+            .collect();
+        let saved = instances
+            .iter()
+            .find(|instance| instance.get_instance_id() == tunnel.instance_id);
+        let replacement = instances
+            .iter()
+            .find(|instance| instance.get_name() == tunnel.instance_name);
+        let (instance, question) = match (saved, replacement) {
+            (Some(instance), _) => (instance.clone(), None),
+            (None, Some(instance)) => {
+                let question = format!(
+                    "{} ({}) is not running anymore. Tunnel through {} ({}) instead?",
+                    tunnel.instance_name,
+                    tunnel.instance_id,
+                    instance.get_name(),
+                    instance.get_instance_id()
+                );
+                (instance.clone(), Some(question))
+            }
+            (None, None) => return Err(RuntimeError::BastionNotFound(tunnel.instance_name).into()),
+        };
+        let session = Session::PortForward {
+            instance,
+            remote_host: tunnel.remote_host,
+            remote_port: tunnel.remote_port,
+            local_port: tunnel.local_port,
+        };
+        Ok((session, question))
+    }
+}
+
+/// RDS instances and Aurora clusters of a region, offered as tunnel targets
+#[derive(Debug)]
+pub struct DatabaseDiscovery {
+    task: JoinHandle<Result<Vec<DatabaseInfo>>>,
+}
+
+impl DatabaseDiscovery {
+    pub fn start(
+        provider: &Arc<dyn InventoryProvider>,
+        region: Region,
+        profile: Option<String>,
+    ) -> DatabaseDiscovery {
+        let provider = provider.clone();
+        let task = tokio::spawn(async move { provider.fetch_databases(region, profile).await });
+        DatabaseDiscovery { task }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.task.is_finished()
+    }
+
+    pub fn cancel(self) {
+        self.task.abort();
+    }
+
+    pub async fn finish(self) -> Result<Vec<DatabaseInfo>> {
+        self.task.await?
+    }
+}
+
+/// The tunnel list entry of a port forwarding session
+pub fn get_tunnel(session: &Session) -> Option<Tunnel> {
+    let Session::PortForward {
+        instance,
+        remote_host,
+        remote_port,
+        local_port,
+    } = session
+    else {
+        return None;
+    };
+    Some(Tunnel {
+        instance_id: instance.get_instance_id(),
+        instance_name: instance.get_name(),
+        region: instance.get_region().to_string(),
+        profile: instance.get_profile(),
+        remote_host: remote_host.clone(),
+        remote_port: *remote_port,
+        local_port: *local_port,
+    })
+}
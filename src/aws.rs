@@ -1,22 +1,224 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
 
 use anyhow::Result;
-use aws_config::{BehaviorVersion, Region};
+use aws_config::{
+    meta::region::RegionProviderChain, profile::ProfileFileRegionProvider, BehaviorVersion,
+    Region, SdkConfig,
+};
 use aws_sdk_ec2::{
-    types::{Filter, Instance},
+    error::{DisplayErrorContext, ProvideErrorMetadata, SdkError},
+    primitives::{DateTime, DateTimeFormat},
+    types::{
+        Filter, GroupIdentifier, Instance, InstanceState, InstanceStateName, InstanceType,
+        PlatformValues, Tag,
+    },
     Client,
 };
 
+use async_trait::async_trait;
+use aws_credential_types::provider::error::{CredentialsError, TokenError};
+use aws_sdk_sso::{
+    error::SdkError as SsoSdkError,
+    operation::get_role_credentials::GetRoleCredentialsError,
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
 use crate::history::History;
 
+pub mod fixture;
+pub mod profiles;
+pub mod rds;
+pub mod run_command;
+
+#[derive(Error, Debug)]
+pub enum AwsError {
+    #[error("The SSO session is expired or missing, run `aws sso login` to start a new one")]
+    ExpiredSso,
+    #[error("No AWS credentials found, configure a profile or set AWS_PROFILE")]
+    MissingCredentials,
+    #[error("Access denied: {0}")]
+    AccessDenied(String),
+    #[error("AWS is throttling requests, try again in a moment")]
+    Throttled,
+    #[error("{0}")]
+    Other(String),
+}
+
+impl AwsError {
+    /// Sorts SDK errors into the cases the user can act upon
+    pub fn from_sdk_error<E, R>(error: SdkError<E, R>) -> AwsError
+    where
+        E: ProvideErrorMetadata + std::error::Error + 'static,
+        R: std::fmt::Debug + 'static,
+    {
+        let message = DisplayErrorContext(&error).to_string();
+        match error.code() {
+            Some(
+                "AuthFailure"
+                | "UnauthorizedOperation"
+                | "AccessDenied"
+                | "AccessDeniedException"
+                | "OptInRequired",
+            ) => AwsError::AccessDenied(error.message().unwrap_or(&message).to_string()),
+            Some("RequestLimitExceeded" | "Throttling" | "ThrottlingException") => {
+                AwsError::Throttled
+            }
+            Some("ExpiredToken" | "ExpiredTokenException") => AwsError::ExpiredSso,
+            Some(_) => AwsError::Other(message),
+            // Credential failures happen before the request is sent, so there is no error code
+            None => Self::from_credentials_error(&error).unwrap_or(AwsError::Other(message)),
+        }
+    }
+
+    /// Looks through the causes of the error for the credentials provider failing
+    fn from_credentials_error(error: &(dyn std::error::Error + 'static)) -> Option<AwsError> {
+        let mut source = Some(error);
+        while let Some(error) = source {
+            // Only the SSO token provider resolves tokens while loading credentials
+            if error.is::<TokenError>() {
+                return Some(AwsError::ExpiredSso);
+            }
+            if let Some(sso_error) = error.downcast_ref::<SsoSdkError<GetRoleCredentialsError>>()
+                && let Some(GetRoleCredentialsError::UnauthorizedException(_)) =
+                    sso_error.as_service_error()
+            {
+                return Some(AwsError::ExpiredSso);
+            }
+            if let Some(CredentialsError::CredentialsNotLoaded(_)) =
+                error.downcast_ref::<CredentialsError>()
+            {
+                return Some(AwsError::MissingCredentials);
+            }
+            source = error.source();
+        }
+        None
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InstanceStateChange {
+    Start,
+    Stop,
+    Reboot,
+}
+
+impl InstanceStateChange {
+    /// The changes that make sense for an instance in the given state
+    pub fn available_for(state: &str) -> Vec<InstanceStateChange> {
+        match state {
+            "running" => vec![InstanceStateChange::Stop, InstanceStateChange::Reboot],
+            "stopped" => vec![InstanceStateChange::Start],
+            _ => vec![],
+        }
+    }
+}
+
+impl From<InstanceStateChange> for String {
+    fn from(change: InstanceStateChange) -> String {
+        match change {
+            InstanceStateChange::Start => "Start".to_string(),
+            InstanceStateChange::Stop => "Stop".to_string(),
+            InstanceStateChange::Reboot => "Reboot".to_string(),
+        }
+    }
+}
+
+/// Session Manager registration of an instance, from DescribeInstanceInformation
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ManagedNode {
+    pub ping_status: String,
+    pub agent_version: String,
+    pub platform_type: String,
+    pub platform_name: String,
+    pub last_ping: String,
+}
+
+impl ManagedNode {
+    pub fn is_online(&self) -> bool {
+        self.ping_status == "Online"
+    }
+}
+
+/// Flat, serializable view of an instance, used wherever instances leave the TUI
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstanceRecord {
+    pub name: String,
+    pub instance_id: String,
+    pub region: String,
+    pub private_ip: String,
+    pub public_ip: String,
+    pub instance_type: String,
+    pub state: String,
+    pub vpc_id: String,
+    pub launch_time: String,
+    #[serde(default)]
+    pub image_id: String,
+    #[serde(default)]
+    pub security_groups: Vec<String>,
+    pub tags: BTreeMap<String, String>,
+    pub last_access: Option<u64>,
+    pub managed_node: Option<ManagedNode>,
+    /// Why the Session Manager status couldn't be fetched
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ssm_error: Option<String>,
+}
+
 #[derive(Debug, Clone)]
 pub struct InstanceInfo {
     region: Region,
+    profile: Option<String>,
     raw_instance_data: Instance,
     last_access: Option<u64>,
+    managed_node: Option<ManagedNode>,
+    /// Set when DescribeInstanceInformation failed, the SSM status is then unknown
+    ssm_error: Option<String>,
 }
 
 impl InstanceInfo {
+    /// Rebuilds an instance from its record, as saved in the inventory cache
+    pub fn from_record(record: InstanceRecord, profile: Option<String>) -> InstanceInfo {
+        let raw_instance_data = Instance::builder()
+            .instance_id(record.instance_id)
+            .private_ip_address(record.private_ip)
+            .public_ip_address(record.public_ip)
+            .instance_type(InstanceType::from(record.instance_type.as_str()))
+            .state(
+                InstanceState::builder()
+                    .name(InstanceStateName::from(record.state.as_str()))
+                    .build(),
+            )
+            .vpc_id(record.vpc_id)
+            .set_launch_time(
+                DateTime::from_str(&record.launch_time, DateTimeFormat::DateTime).ok(),
+            )
+            .image_id(record.image_id)
+            .set_security_groups(Some(
+                record
+                    .security_groups
+                    .into_iter()
+                    .map(|name| GroupIdentifier::builder().group_name(name).build())
+                    .collect(),
+            ))
+            .set_tags(Some(
+                record
+                    .tags
+                    .into_iter()
+                    .map(|(key, value)| Tag::builder().key(key).value(value).build())
+                    .collect(),
+            ))
+            .build();
+        InstanceInfo {
+            region: Region::new(record.region),
+            profile,
+            raw_instance_data,
+            last_access: record.last_access,
+            managed_node: record.managed_node,
+            ssm_error: record.ssm_error,
+        }
+    }
+
     pub fn get_name(&self) -> String {
         self.get_tags()
             .entry("Name".to_string())
@@ -63,6 +265,10 @@ impl InstanceInfo {
         self.region.clone()
     }
 
+    pub fn get_profile(&self) -> Option<String> {
+        self.profile.clone()
+    }
+
     pub fn get_image_id(&self) -> String {
         self.raw_instance_data.image_id.clone().unwrap_or_default()
     }
@@ -85,6 +291,18 @@ impl InstanceInfo {
             .map_or(String::default(), |x| x.to_string())
     }
 
+    pub fn get_state(&self) -> String {
+        self.raw_instance_data
+            .state
+            .as_ref()
+            .and_then(|state| state.name.as_ref())
+            .map_or(String::default(), |x| x.as_str().to_string())
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.get_state() == "running"
+    }
+
     pub fn get_vpc_id(&self) -> String {
         self.raw_instance_data.vpc_id.clone().unwrap_or_default()
     }
@@ -103,40 +321,453 @@ impl InstanceInfo {
     pub fn get_last_access(&self) -> Option<u64> {
         self.last_access
     }
+
+    pub fn get_managed_node(&self) -> Option<ManagedNode> {
+        self.managed_node.clone()
+    }
+
+    /// Why the Session Manager status of the instance is unknown, if it is
+    pub fn get_ssm_error(&self) -> Option<String> {
+        self.ssm_error.clone()
+    }
+
+    pub fn is_ssm_unknown(&self) -> bool {
+        self.ssm_error.is_some()
+    }
+
+    /// Whether Session Manager can currently open a session on the instance
+    pub fn is_ssm_online(&self) -> bool {
+        self.managed_node
+            .as_ref()
+            .is_some_and(|node| node.is_online())
+    }
+
+    /// From the EC2 platform, or the platform the SSM agent reports
+    pub fn is_windows(&self) -> bool {
+        self.raw_instance_data.platform == Some(PlatformValues::Windows)
+            || self
+                .managed_node
+                .as_ref()
+                .is_some_and(|node| node.platform_type == "Windows")
+    }
+
+    pub fn get_ssm_status(&self) -> String {
+        match (&self.managed_node, &self.ssm_error) {
+            (Some(node), _) => node.ping_status.clone(),
+            (None, Some(_)) => "Unknown".to_string(),
+            (None, None) => "Not managed".to_string(),
+        }
+    }
+
+    pub fn to_record(&self) -> InstanceRecord {
+        InstanceRecord {
+            name: self.get_name(),
+            instance_id: self.get_instance_id(),
+            region: self.get_region().to_string(),
+            private_ip: self.get_private_ip(),
+            public_ip: self.get_public_ip(),
+            instance_type: self.get_instance_type(),
+            state: self.get_state(),
+            vpc_id: self.get_vpc_id(),
+            launch_time: self.get_launch_time(),
+            image_id: self.get_image_id(),
+            security_groups: self.get_security_groups(),
+            tags: self.get_tags().into_iter().collect(),
+            last_access: self.get_last_access(),
+            managed_node: self.get_managed_node(),
+            ssm_error: self.get_ssm_error(),
+        }
+    }
+}
+
+/// Where the regions and instances come from, so the app can run without an AWS account
+#[async_trait]
+pub trait InventoryProvider: Send + Sync + std::fmt::Debug {
+    /// Whether the inventory is that of a real account, whose regions are worth remembering
+    fn is_live(&self) -> bool {
+        true
+    }
+
+    async fn fetch_regions(&self, profile: Option<String>) -> Result<Vec<RegionStatus>>;
+
+    /// Every instance of the region that isn't terminated
+    async fn fetch_instances(
+        &self,
+        region: Region,
+        profile: Option<String>,
+    ) -> Result<Vec<InstanceInfo>>;
+
+    /// Fetches the instance again, `None` when it doesn't exist anymore
+    async fn fetch_instance(&self, instance: &InstanceInfo) -> Result<Option<InstanceInfo>>;
+
+    async fn change_instance_state(
+        &self,
+        instance: &InstanceInfo,
+        change: InstanceStateChange,
+    ) -> Result<()>;
+
+    /// RDS instances and Aurora clusters of the region
+    async fn fetch_databases(
+        &self,
+        region: Region,
+        profile: Option<String>,
+    ) -> Result<Vec<rds::DatabaseInfo>>;
+
+    /// Runs the command with `document` on instances of a single region, returns the command id
+    async fn send_command(
+        &self,
+        instances: &[InstanceInfo],
+        document: &str,
+        command: &str,
+    ) -> Result<String>;
+
+    /// Invocations of a command sent to instances of a single region, by instance id
+    async fn get_command_invocations(
+        &self,
+        instances: &[InstanceInfo],
+        command_id: &str,
+    ) -> Result<HashMap<String, run_command::CommandInvocation>>;
+
+    /// Authorizes the public key for `os_user` on the instance, for the next 60 seconds
+    async fn send_ssh_public_key(
+        &self,
+        instance: &InstanceInfo,
+        os_user: &str,
+        public_key: &str,
+    ) -> Result<()>;
+}
+
+/// Inventory backed by the EC2, SSM, RDS and EC2 Instance Connect APIs
+#[derive(Debug, Default)]
+pub struct AwsInventory {
+    /// Replaces the AWS endpoints, to talk to an emulator such as LocalStack
+    endpoint_url: Option<String>,
+    /// By region and profile, so polling commands doesn't resolve credentials every time
+    ssm_clients: Mutex<HashMap<(String, Option<String>), aws_sdk_ssm::Client>>,
+}
+
+impl AwsInventory {
+    pub fn new(endpoint_url: Option<String>) -> AwsInventory {
+        AwsInventory {
+            endpoint_url,
+            ..Default::default()
+        }
+    }
+
+    /// SSM client of the region and profile of the instance, created on first use
+    async fn get_ssm_client(&self, instance: &InstanceInfo) -> aws_sdk_ssm::Client {
+        let key = (instance.get_region().to_string(), instance.get_profile());
+        if let Some(client) = self.ssm_clients.lock().unwrap().get(&key) {
+            return client.clone();
+        }
+        let config =
+            load_sdk_config(instance.get_region(), &instance.get_profile(), &self.endpoint_url)
+                .await;
+        let client = aws_sdk_ssm::Client::new(&config);
+        self.ssm_clients.lock().unwrap().insert(key, client.clone());
+        client
+    }
+}
+
+#[async_trait]
+impl InventoryProvider for AwsInventory {
+    async fn fetch_regions(&self, profile: Option<String>) -> Result<Vec<RegionStatus>> {
+        fetch_regions(profile, &self.endpoint_url).await
+    }
+
+    async fn fetch_instances(
+        &self,
+        region: Region,
+        profile: Option<String>,
+    ) -> Result<Vec<InstanceInfo>> {
+        fetch_instances(region, profile, &self.endpoint_url).await
+    }
+
+    async fn fetch_instance(&self, instance: &InstanceInfo) -> Result<Option<InstanceInfo>> {
+        fetch_instance(instance, &self.endpoint_url).await
+    }
+
+    async fn change_instance_state(
+        &self,
+        instance: &InstanceInfo,
+        change: InstanceStateChange,
+    ) -> Result<()> {
+        change_instance_state(instance, change, &self.endpoint_url).await
+    }
+
+    async fn fetch_databases(
+        &self,
+        region: Region,
+        profile: Option<String>,
+    ) -> Result<Vec<rds::DatabaseInfo>> {
+        rds::fetch_databases(region, profile, &self.endpoint_url).await
+    }
+
+    async fn send_command(
+        &self,
+        instances: &[InstanceInfo],
+        document: &str,
+        command: &str,
+    ) -> Result<String> {
+        let Some(first) = instances.first() else {
+            return Ok(String::new());
+        };
+        let client = self.get_ssm_client(first).await;
+        run_command::send_command(&client, instances, document, command).await
+    }
+
+    async fn get_command_invocations(
+        &self,
+        instances: &[InstanceInfo],
+        command_id: &str,
+    ) -> Result<HashMap<String, run_command::CommandInvocation>> {
+        let Some(first) = instances.first() else {
+            return Ok(HashMap::new());
+        };
+        let client = self.get_ssm_client(first).await;
+        run_command::get_command_invocations(&client, instances, command_id).await
+    }
+
+    async fn send_ssh_public_key(
+        &self,
+        instance: &InstanceInfo,
+        os_user: &str,
+        public_key: &str,
+    ) -> Result<()> {
+        send_ssh_public_key(instance, os_user, public_key, &self.endpoint_url).await
+    }
+}
+
+/// Whether the account can use a region, as reported by DescribeRegions
+#[derive(Debug, Clone)]
+pub struct RegionStatus {
+    pub name: String,
+    pub enabled: bool,
 }
 
-pub async fn fetch_instances(region: Region) -> Result<Vec<InstanceInfo>> {
-    let config = aws_config::defaults(BehaviorVersion::latest())
-        .region(region.clone())
-        .load()
-        .await;
+async fn load_sdk_config(
+    region: Region,
+    profile: &Option<String>,
+    endpoint_url: &Option<String>,
+) -> SdkConfig {
+    let mut loader = aws_config::defaults(BehaviorVersion::latest()).region(region);
+    if let Some(profile) = profile {
+        loader = loader.profile_name(profile);
+    }
+    if let Some(endpoint_url) = endpoint_url {
+        loader = loader.endpoint_url(endpoint_url);
+    }
+    loader.load().await
+}
+
+/// Lists every region of the partition, including the opt-in ones the account hasn't enabled.
+/// The call goes to the profile's default region, or us-east-1 when it has none.
+async fn fetch_regions(
+    profile: Option<String>,
+    endpoint_url: &Option<String>,
+) -> Result<Vec<RegionStatus>> {
+    let mut profile_region = ProfileFileRegionProvider::builder();
+    if let Some(profile) = &profile {
+        profile_region = profile_region.profile_name(profile);
+    }
+    let region = RegionProviderChain::first_try(profile_region.build())
+        .or_default_provider()
+        .region()
+        .await
+        .unwrap_or(Region::new("us-east-1"));
+    let config = load_sdk_config(region, &profile, endpoint_url).await;
     let client = Client::new(&config);
+    let result = client
+        .describe_regions()
+        .all_regions(true)
+        .send()
+        .await
+        .map_err(AwsError::from_sdk_error)?;
+    Ok(result
+        .regions()
+        .iter()
+        .filter_map(|region| {
+            Some(RegionStatus {
+                name: region.region_name()?.to_string(),
+                enabled: region.opt_in_status() != Some("not-opted-in"),
+            })
+        })
+        .collect())
+}
+
+/// Fetches every instance of the region that isn't terminated, in any other state
+async fn fetch_instances(
+    region: Region,
+    profile: Option<String>,
+    endpoint_url: &Option<String>,
+) -> Result<Vec<InstanceInfo>> {
     let filters = vec![Filter::builder()
         .set_name(Some("instance-state-name".to_string()))
-        .set_values(Some(vec!["running".to_string()]))
+        .set_values(Some(
+            ["pending", "running", "stopping", "stopped", "shutting-down"]
+                .map(String::from)
+                .to_vec(),
+        ))
         .build()];
-    let result = client
+    describe_instances(region, profile, endpoint_url, filters).await
+}
+
+/// Fetches a single instance, returning `None` when it doesn't exist anymore
+async fn fetch_instance(
+    instance: &InstanceInfo,
+    endpoint_url: &Option<String>,
+) -> Result<Option<InstanceInfo>> {
+    // Unlike InstanceIds, which fails with InvalidInstanceID.NotFound, a filter on an instance
+    // that is gone returns nothing
+    let filter = Filter::builder()
+        .name("instance-id")
+        .values(instance.get_instance_id())
+        .build();
+    let instances = describe_instances(
+        instance.get_region(),
+        instance.get_profile(),
+        endpoint_url,
+        vec![filter],
+    )
+    .await?;
+    Ok(instances.into_iter().next())
+}
+
+async fn describe_instances(
+    region: Region,
+    profile: Option<String>,
+    endpoint_url: &Option<String>,
+    filters: Vec<Filter>,
+) -> Result<Vec<InstanceInfo>> {
+    let config = load_sdk_config(region.clone(), &profile, endpoint_url).await;
+    let client = Client::new(&config);
+    let reservations = client
         .describe_instances()
         .set_filters(Some(filters))
+        .into_paginator()
+        .items()
         .send()
-        .await?;
+        .try_collect()
+        .await
+        .map_err(AwsError::from_sdk_error)?;
 
-    let binding = result.reservations.unwrap();
+    // Session Manager status is a nice to have, don't lose the instance list over it
+    let (managed_nodes, ssm_error) = match fetch_managed_nodes(&config).await {
+        Ok(managed_nodes) => (managed_nodes, None),
+        Err(e) => (HashMap::new(), Some(format!("{:#}", e))),
+    };
     let recents = History::read()?;
-    let instances: Vec<InstanceInfo> = binding
-        .iter()
-        .flat_map(|reservation| reservation.instances.clone().unwrap())
+    let instances: Vec<InstanceInfo> = reservations
+        .into_iter()
+        .flat_map(|reservation| reservation.instances.unwrap_or_default())
         .map(|instance: Instance| {
-            let cloned = instance.clone();
-            let last_accessed = recents
-                .get(&instance.instance_id.clone().unwrap_or_default())
+            let last_accessed = instance
+                .instance_id
+                .as_ref()
+                .and_then(|id| recents.get(id))
                 .map(|entry| entry.get_when());
+            let managed_node = instance
+                .instance_id
+                .as_ref()
+                .and_then(|id| managed_nodes.get(id))
+                .cloned();
             InstanceInfo {
                 region: region.clone(),
-                raw_instance_data: cloned,
+                profile: profile.clone(),
+                raw_instance_data: instance,
                 last_access: last_accessed,
+                managed_node,
+                ssm_error: ssm_error.clone(),
             }
         })
         .collect();
     Ok(instances)
 }
+
+async fn change_instance_state(
+    instance: &InstanceInfo,
+    change: InstanceStateChange,
+    endpoint_url: &Option<String>,
+) -> Result<()> {
+    let config =
+        load_sdk_config(instance.get_region(), &instance.get_profile(), endpoint_url).await;
+    let client = Client::new(&config);
+    let instance_id = instance.get_instance_id();
+    match change {
+        InstanceStateChange::Start => client
+            .start_instances()
+            .instance_ids(instance_id)
+            .send()
+            .await
+            .map(|_| ())
+            .map_err(AwsError::from_sdk_error)?,
+        InstanceStateChange::Stop => client
+            .stop_instances()
+            .instance_ids(instance_id)
+            .send()
+            .await
+            .map(|_| ())
+            .map_err(AwsError::from_sdk_error)?,
+        InstanceStateChange::Reboot => client
+            .reboot_instances()
+            .instance_ids(instance_id)
+            .send()
+            .await
+            .map(|_| ())
+            .map_err(AwsError::from_sdk_error)?,
+    }
+    Ok(())
+}
+
+/// Pushes the key with EC2 Instance Connect
+async fn send_ssh_public_key(
+    instance: &InstanceInfo,
+    os_user: &str,
+    public_key: &str,
+    endpoint_url: &Option<String>,
+) -> Result<()> {
+    let config =
+        load_sdk_config(instance.get_region(), &instance.get_profile(), endpoint_url).await;
+    let client = aws_sdk_ec2instanceconnect::Client::new(&config);
+    client
+        .send_ssh_public_key()
+        .instance_id(instance.get_instance_id())
+        .instance_os_user(os_user)
+        .ssh_public_key(public_key)
+        .send()
+        .await
+        .map_err(AwsError::from_sdk_error)?;
+    Ok(())
+}
+
+/// Returns the nodes registered with Session Manager in the region, by instance id
+async fn fetch_managed_nodes(config: &SdkConfig) -> Result<HashMap<String, ManagedNode>> {
+    let client = aws_sdk_ssm::Client::new(config);
+    let nodes = client
+        .describe_instance_information()
+        .into_paginator()
+        .items()
+        .send()
+        .try_collect()
+        .await
+        .map_err(AwsError::from_sdk_error)?;
+    Ok(nodes
+        .into_iter()
+        .filter_map(|node| {
+            let managed_node = ManagedNode {
+                ping_status: node
+                    .ping_status()
+                    .map_or(String::default(), |x| x.as_str().to_string()),
+                agent_version: node.agent_version().unwrap_or_default().to_string(),
+                platform_type: node
+                    .platform_type()
+                    .map_or(String::default(), |x| x.as_str().to_string()),
+                platform_name: node.platform_name().unwrap_or_default().to_string(),
+                last_ping: node
+                    .last_ping_date_time()
+                    .map_or(String::default(), |x| x.to_string()),
+            };
+            Some((node.instance_id?, managed_node))
+        })
+        .collect())
+}
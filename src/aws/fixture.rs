@@ -0,0 +1,218 @@
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
+use std::sync::Mutex;
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use aws_config::Region;
+
+use super::rds::DatabaseInfo;
+use super::run_command::CommandInvocation;
+use super::{InstanceInfo, InstanceRecord, InstanceStateChange, InventoryProvider, RegionStatus};
+
+/// Inventory read from a JSON file, in the format of `sm_connect list --format json`.
+/// State changes only live in memory, and commands aren't run.
+#[derive(Debug)]
+pub struct FixtureInventory {
+    records: Mutex<Vec<InstanceRecord>>,
+    /// Commands sent, the command id is the index
+    commands: Mutex<Vec<String>>,
+}
+
+impl FixtureInventory {
+    pub fn new(records: Vec<InstanceRecord>) -> FixtureInventory {
+        FixtureInventory {
+            records: Mutex::new(records),
+            commands: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<FixtureInventory> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("could not read fixture {}", path.display()))?;
+        let records = serde_json::from_str(&contents)
+            .with_context(|| format!("invalid fixture {}", path.display()))?;
+        Ok(FixtureInventory::new(records))
+    }
+}
+
+#[async_trait]
+impl InventoryProvider for FixtureInventory {
+    fn is_live(&self) -> bool {
+        false
+    }
+
+    /// The regions the fixture has instances in
+    async fn fetch_regions(&self, _profile: Option<String>) -> Result<Vec<RegionStatus>> {
+        let records = self.records.lock().unwrap();
+        let regions: BTreeSet<&String> = records.iter().map(|record| &record.region).collect();
+        Ok(regions
+            .into_iter()
+            .map(|name| RegionStatus {
+                name: name.clone(),
+                enabled: true,
+            })
+            .collect())
+    }
+
+    async fn fetch_instances(
+        &self,
+        region: Region,
+        profile: Option<String>,
+    ) -> Result<Vec<InstanceInfo>> {
+        let records = self.records.lock().unwrap();
+        Ok(records
+            .iter()
+            .filter(|record| record.region == region.as_ref() && record.state != "terminated")
+            .map(|record| InstanceInfo::from_record(record.clone(), profile.clone()))
+            .collect())
+    }
+
+    async fn fetch_instance(&self, instance: &InstanceInfo) -> Result<Option<InstanceInfo>> {
+        let records = self.records.lock().unwrap();
+        Ok(records
+            .iter()
+            .find(|record| record.instance_id == instance.get_instance_id())
+            .map(|record| InstanceInfo::from_record(record.clone(), instance.get_profile())))
+    }
+
+    async fn change_instance_state(
+        &self,
+        instance: &InstanceInfo,
+        change: InstanceStateChange,
+    ) -> Result<()> {
+        let mut records = self.records.lock().unwrap();
+        if let Some(record) = records
+            .iter_mut()
+            .find(|record| record.instance_id == instance.get_instance_id())
+        {
+            match change {
+                InstanceStateChange::Start => record.state = "running".to_string(),
+                InstanceStateChange::Stop => record.state = "stopped".to_string(),
+                InstanceStateChange::Reboot => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Fixtures only describe instances
+    async fn fetch_databases(
+        &self,
+        _region: Region,
+        _profile: Option<String>,
+    ) -> Result<Vec<DatabaseInfo>> {
+        Ok(Vec::new())
+    }
+
+    async fn send_command(
+        &self,
+        _instances: &[InstanceInfo],
+        _document: &str,
+        command: &str,
+    ) -> Result<String> {
+        let mut commands = self.commands.lock().unwrap();
+        commands.push(command.to_string());
+        Ok((commands.len() - 1).to_string())
+    }
+
+    /// Every command succeeds, echoing what would have run
+    async fn get_command_invocations(
+        &self,
+        instances: &[InstanceInfo],
+        command_id: &str,
+    ) -> Result<HashMap<String, CommandInvocation>> {
+        let commands = self.commands.lock().unwrap();
+        let command = command_id
+            .parse::<usize>()
+            .ok()
+            .and_then(|index| commands.get(index))
+            .context("unknown command id")?;
+        Ok(instances
+            .iter()
+            .map(|instance| {
+                let invocation = CommandInvocation {
+                    status: "Success".to_string(),
+                    stdout: format!("{} would run `{}`\n", instance.get_name(), command),
+                    stderr: String::new(),
+                };
+                (instance.get_instance_id(), invocation)
+            })
+            .collect())
+    }
+
+    /// Fixture instances accept any key
+    async fn send_ssh_public_key(
+        &self,
+        _instance: &InstanceInfo,
+        _os_user: &str,
+        _public_key: &str,
+    ) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+impl FixtureInventory {
+    /// The instances of fixtures/instances.json
+    pub fn test_instances() -> Vec<InstanceInfo> {
+        let records: Vec<InstanceRecord> =
+            serde_json::from_str(include_str!("../../fixtures/instances.json")).unwrap();
+        records
+            .into_iter()
+            .map(|record| InstanceInfo::from_record(record, None))
+            .collect()
+    }
+
+    pub fn test_records() -> Vec<InstanceRecord> {
+        Self::test_instances()
+            .iter()
+            .map(|instance| instance.to_record())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn regions_come_from_the_records() {
+        let provider = FixtureInventory::new(FixtureInventory::test_records());
+        let regions: Vec<String> = provider
+            .fetch_regions(None)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|region| region.name)
+            .collect();
+        assert_eq!(regions, ["eu-west-1", "us-east-1"]);
+    }
+
+    #[tokio::test]
+    async fn instances_of_a_region() {
+        let mut records = FixtureInventory::test_records();
+        records[1].state = "terminated".to_string();
+        let provider = FixtureInventory::new(records);
+        let instances = provider
+            .fetch_instances(Region::new("eu-west-1"), Some("prod".to_string()))
+            .await
+            .unwrap();
+        let names: Vec<String> = instances.iter().map(|instance| instance.get_name()).collect();
+        assert_eq!(names, ["web-1", "batch"]);
+        assert!(instances
+            .iter()
+            .all(|instance| instance.get_profile() == Some("prod".to_string())));
+    }
+
+    #[tokio::test]
+    async fn state_changes_stay_in_memory() {
+        let provider = FixtureInventory::new(FixtureInventory::test_records());
+        let batch = &FixtureInventory::test_instances()[2];
+        provider
+            .change_instance_state(batch, InstanceStateChange::Start)
+            .await
+            .unwrap();
+        let batch = provider.fetch_instance(batch).await.unwrap().unwrap();
+        assert_eq!(batch.get_state(), "running");
+    }
+}
@@ -0,0 +1,113 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::PathBuf,
+};
+
+use anyhow::Result;
+use configparser::ini::Ini;
+use home::home_dir;
+
+type Sections = HashMap<String, HashMap<String, Option<String>>>;
+
+#[derive(Debug, Clone, Default)]
+pub struct AwsProfile {
+    name: String,
+    sso_start_url: Option<String>,
+    role_arn: Option<String>,
+    region: Option<String>,
+}
+
+impl AwsProfile {
+    pub fn get_name(&self) -> String {
+        self.name.clone()
+    }
+
+    pub fn get_sso_start_url(&self) -> Option<String> {
+        self.sso_start_url.clone()
+    }
+
+    pub fn get_role_arn(&self) -> Option<String> {
+        self.role_arn.clone()
+    }
+
+    pub fn get_region(&self) -> Option<String> {
+        self.region.clone()
+    }
+}
+
+/// Reads the profiles defined in the AWS config and credentials files, sorted by name.
+/// Missing files are treated as empty.
+pub fn load_profiles() -> Result<Vec<AwsProfile>> {
+    let config = read_sections(get_aws_file_path("AWS_CONFIG_FILE", "config")?)?;
+    let credentials = read_sections(get_aws_file_path(
+        "AWS_SHARED_CREDENTIALS_FILE",
+        "credentials",
+    )?)?;
+
+    let mut profiles: BTreeMap<String, AwsProfile> = BTreeMap::new();
+    for (section, values) in &config {
+        // The config file prefixes every profile but the default one with "profile "
+        let name = match section.strip_prefix("profile ") {
+            Some(name) => name.trim(),
+            None if section == "default" => section,
+            None => continue,
+        };
+        let sso_start_url = get_value(values, "sso_start_url").or_else(|| {
+            get_value(values, "sso_session").and_then(|session| {
+                config
+                    .get(&format!("sso-session {}", session))
+                    .and_then(|session| get_value(session, "sso_start_url"))
+            })
+        });
+        profiles.insert(
+            name.to_string(),
+            AwsProfile {
+                name: name.to_string(),
+                sso_start_url,
+                role_arn: get_value(values, "role_arn"),
+                region: get_value(values, "region"),
+            },
+        );
+    }
+    for (name, values) in &credentials {
+        let profile = profiles.entry(name.clone()).or_insert_with(|| AwsProfile {
+            name: name.clone(),
+            ..Default::default()
+        });
+        if profile.region.is_none() {
+            profile.region = get_value(values, "region");
+        }
+    }
+    Ok(profiles.into_values().collect())
+}
+
+/// Profile the SDK and the aws CLI end up using: the one picked in sm_connect, else
+/// AWS_PROFILE, else "default"
+pub fn get_effective_profile(profile: &Option<String>) -> String {
+    profile
+        .clone()
+        .or_else(|| std::env::var("AWS_PROFILE").ok())
+        .filter(|profile| !profile.is_empty())
+        .unwrap_or("default".to_string())
+}
+
+fn get_value(values: &HashMap<String, Option<String>>, key: &str) -> Option<String> {
+    values.get(key).cloned().flatten()
+}
+
+fn read_sections(path: PathBuf) -> Result<Sections> {
+    if !path.exists() {
+        return Ok(Sections::new());
+    }
+    Ini::new_cs().load(path).map_err(|e| anyhow::anyhow!(e))
+}
+
+fn get_aws_file_path(env_var: &str, file_name: &str) -> Result<PathBuf> {
+    if let Ok(path) = std::env::var(env_var) {
+        return Ok(PathBuf::from(path));
+    }
+    let Some(home_dir) = home_dir() else {
+        return Result::Err(anyhow::anyhow!("Could not find home directory"));
+    };
+    Ok(home_dir.join(".aws").join(file_name))
+}
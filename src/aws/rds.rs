@@ -0,0 +1,109 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use aws_config::Region;
+
+use super::{load_sdk_config, AwsError};
+
+/// RDS instance or Aurora cluster endpoint that can be tunneled to
+#[derive(Debug, Clone)]
+pub struct DatabaseInfo {
+    pub identifier: String,
+    /// "instance", "cluster" or "cluster (reader)"
+    pub kind: String,
+    pub engine: String,
+    pub status: String,
+    pub endpoint: String,
+    pub port: u16,
+    pub vpc_id: String,
+    pub region: Region,
+}
+
+/// Lists the DB instances of the region, and the writer and reader endpoints of its clusters.
+/// Databases without an endpoint yet, e.g. still being created, are left out.
+pub async fn fetch_databases(
+    region: Region,
+    profile: Option<String>,
+    endpoint_url: &Option<String>,
+) -> Result<Vec<DatabaseInfo>> {
+    let config = load_sdk_config(region.clone(), &profile, endpoint_url).await;
+    let client = aws_sdk_rds::Client::new(&config);
+    let instances = client
+        .describe_db_instances()
+        .into_paginator()
+        .items()
+        .send()
+        .try_collect()
+        .await
+        .map_err(AwsError::from_sdk_error)?;
+    let clusters = client
+        .describe_db_clusters()
+        .into_paginator()
+        .items()
+        .send()
+        .try_collect()
+        .await
+        .map_err(AwsError::from_sdk_error)?;
+    // Clusters only name their subnet group, which may have no member instance to go by
+    let subnet_group_vpcs: HashMap<String, String> = client
+        .describe_db_subnet_groups()
+        .into_paginator()
+        .items()
+        .send()
+        .try_collect()
+        .await
+        .map_err(AwsError::from_sdk_error)?
+        .into_iter()
+        .filter_map(|group| Some((group.db_subnet_group_name?, group.vpc_id?)))
+        .collect();
+
+    let mut databases = Vec::new();
+    for instance in instances {
+        let vpc_id = instance
+            .db_subnet_group()
+            .and_then(|group| group.vpc_id())
+            .unwrap_or_default()
+            .to_string();
+        let Some(endpoint) = instance.endpoint() else {
+            continue;
+        };
+        databases.push(DatabaseInfo {
+            identifier: instance.db_instance_identifier().unwrap_or_default().to_string(),
+            kind: "instance".to_string(),
+            engine: instance.engine().unwrap_or_default().to_string(),
+            status: instance.db_instance_status().unwrap_or_default().to_string(),
+            endpoint: endpoint.address().unwrap_or_default().to_string(),
+            port: endpoint.port().unwrap_or_default() as u16,
+            vpc_id,
+            region: region.clone(),
+        });
+    }
+    for cluster in clusters {
+        let identifier = cluster.db_cluster_identifier().unwrap_or_default();
+        let vpc_id = cluster
+            .db_subnet_group()
+            .and_then(|group| subnet_group_vpcs.get(group))
+            .cloned()
+            .unwrap_or_default();
+        let endpoints = [
+            ("cluster", cluster.endpoint()),
+            ("cluster (reader)", cluster.reader_endpoint()),
+        ];
+        for (kind, endpoint) in endpoints {
+            let Some(endpoint) = endpoint else {
+                continue;
+            };
+            databases.push(DatabaseInfo {
+                identifier: identifier.to_string(),
+                kind: kind.to_string(),
+                engine: cluster.engine().unwrap_or_default().to_string(),
+                status: cluster.status().unwrap_or_default().to_string(),
+                endpoint: endpoint.to_string(),
+                port: cluster.port().unwrap_or_default() as u16,
+                vpc_id: vpc_id.clone(),
+                region: region.clone(),
+            });
+        }
+    }
+    Ok(databases)
+}
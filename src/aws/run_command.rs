@@ -0,0 +1,214 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+
+use super::{AwsError, InstanceInfo};
+
+pub const SHELL_DOCUMENT: &str = "AWS-RunShellScript";
+pub const POWERSHELL_DOCUMENT: &str = "AWS-RunPowerShellScript";
+/// Characters of output GetCommandInvocation returns, the rest only goes to S3
+const OUTPUT_LIMIT: usize = 24000;
+
+/// Progress and output of a command on one instance
+#[derive(Debug, Clone, Default)]
+pub struct CommandInvocation {
+    /// As reported by GetCommandInvocation: Pending, InProgress, Success, Failed...
+    pub status: String,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+impl CommandInvocation {
+    pub fn pending() -> CommandInvocation {
+        CommandInvocation {
+            status: "Pending".to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Not sent to the instance, which couldn't have run it
+    pub fn skipped(reason: String) -> CommandInvocation {
+        CommandInvocation {
+            status: "Skipped".to_string(),
+            stderr: reason,
+            ..Default::default()
+        }
+    }
+
+    /// Whether the status won't change anymore
+    pub fn is_finished(&self) -> bool {
+        matches!(
+            self.status.as_str(),
+            "Success" | "Failed" | "Cancelled" | "TimedOut" | "Skipped"
+        )
+    }
+}
+
+/// Why SendCommand would reject the instance, failing the whole batch it is part of
+pub fn get_skip_reason(instance: &InstanceInfo) -> Option<String> {
+    if !instance.is_running() {
+        return Some(format!("The instance is {}", instance.get_state()));
+    }
+    // An unknown status may well be online, SendCommand tells
+    if !instance.is_ssm_online() && !instance.is_ssm_unknown() {
+        return Some(format!(
+            "The instance is not online in Session Manager ({})",
+            instance.get_ssm_status()
+        ));
+    }
+    None
+}
+
+/// The Run Command document able to run a script on the instance
+pub fn get_script_document(instance: &InstanceInfo) -> &'static str {
+    match instance.is_windows() {
+        true => POWERSHELL_DOCUMENT,
+        false => SHELL_DOCUMENT,
+    }
+}
+
+/// Sends the command to instances of a single region, returns the command id
+pub async fn send_command(
+    client: &aws_sdk_ssm::Client,
+    instances: &[InstanceInfo],
+    document: &str,
+    command: &str,
+) -> Result<String> {
+    let output = client
+        .send_command()
+        .set_instance_ids(Some(
+            instances
+                .iter()
+                .map(|instance| instance.get_instance_id())
+                .collect(),
+        ))
+        .document_name(document)
+        .parameters("commands", vec![command.to_string()])
+        .comment("sm_connect")
+        .send()
+        .await
+        .map_err(AwsError::from_sdk_error)?;
+    Ok(output
+        .command()
+        .and_then(|command| command.command_id())
+        .unwrap_or_default()
+        .to_string())
+}
+
+/// Invocations of the command on `instances` by instance id. The statuses come from one
+/// ListCommandInvocations call, the output of finished ones from GetCommandInvocation.
+/// Instances whose agent didn't pick up the command yet are missing.
+pub async fn get_command_invocations(
+    client: &aws_sdk_ssm::Client,
+    instances: &[InstanceInfo],
+    command_id: &str,
+) -> Result<HashMap<String, CommandInvocation>> {
+    let statuses: Vec<_> = client
+        .list_command_invocations()
+        .command_id(command_id)
+        .into_paginator()
+        .items()
+        .send()
+        .try_collect()
+        .await
+        .map_err(AwsError::from_sdk_error)?;
+    let mut invocations = HashMap::new();
+    for status in statuses {
+        let Some(instance_id) = status.instance_id else {
+            continue;
+        };
+        if !instances
+            .iter()
+            .any(|instance| instance.get_instance_id() == instance_id)
+        {
+            continue;
+        }
+        let mut invocation = CommandInvocation {
+            status: status
+                .status
+                .map_or(String::default(), |status| status.as_str().to_string()),
+            ..Default::default()
+        };
+        if invocation.is_finished() {
+            let output = client
+                .get_command_invocation()
+                .command_id(command_id)
+                .instance_id(&instance_id)
+                .send()
+                .await
+                .map_err(AwsError::from_sdk_error)?;
+            invocation.stdout = mark_truncated(output.standard_output_content());
+            invocation.stderr = mark_truncated(output.standard_error_content());
+        }
+        invocations.insert(instance_id, invocation);
+    }
+    Ok(invocations)
+}
+
+fn mark_truncated(output: Option<&str>) -> String {
+    let output = output.unwrap_or_default();
+    match output.chars().count() >= OUTPUT_LIMIT {
+        true => format!("{}\n[truncated]", output),
+        false => output.trim_end().to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aws::fixture::FixtureInventory;
+
+    fn invocation(status: &str) -> CommandInvocation {
+        CommandInvocation {
+            status: status.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn finished_statuses() {
+        for status in ["Success", "Failed", "Cancelled", "TimedOut", "Skipped"] {
+            assert!(invocation(status).is_finished(), "{}", status);
+        }
+        for status in ["Pending", "InProgress", "Delayed", "Cancelling"] {
+            assert!(!invocation(status).is_finished(), "{}", status);
+        }
+        assert!(!CommandInvocation::pending().is_finished());
+        assert!(CommandInvocation::skipped("stopped".to_string()).is_finished());
+    }
+
+    #[test]
+    fn long_output_is_marked() {
+        assert_eq!(mark_truncated(None), "");
+        assert_eq!(mark_truncated(Some("ok\n")), "ok");
+        let output = "x".repeat(OUTPUT_LIMIT);
+        assert_eq!(mark_truncated(Some(&output)), format!("{}\n[truncated]", output));
+    }
+
+    #[test]
+    fn skip_reasons() {
+        let reasons: Vec<(String, Option<String>)> = FixtureInventory::test_instances()
+            .iter()
+            .map(|instance| (instance.get_name(), get_skip_reason(instance)))
+            .collect();
+        assert_eq!(
+            reasons,
+            [
+                ("web-1".to_string(), None),
+                (
+                    "web-2".to_string(),
+                    Some(
+                        "The instance is not online in Session Manager (ConnectionLost)"
+                            .to_string()
+                    )
+                ),
+                ("batch".to_string(), Some("The instance is stopped".to_string())),
+                ("bastion".to_string(), None),
+                (
+                    "legacy".to_string(),
+                    Some("The instance is not online in Session Manager (Not managed)".to_string())
+                ),
+            ]
+        );
+    }
+}
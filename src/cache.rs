@@ -0,0 +1,144 @@
+use anyhow::Result;
+use home::home_dir;
+use serde::{Deserialize, Serialize};
+use serde_json::{from_str, to_string};
+use std::path::{Path, PathBuf};
+
+use crate::aws::profiles::get_effective_profile;
+use crate::aws::{InstanceInfo, InstanceRecord};
+use crate::history::get_current_time;
+
+/// Instances of a profile and region as they were last fetched
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CacheEntry {
+    fetched_at: u64,
+    instances: Vec<InstanceRecord>,
+}
+
+impl CacheEntry {
+    /// Seconds since the instances were fetched
+    pub fn get_age(&self) -> u64 {
+        get_current_time().saturating_sub(self.fetched_at)
+    }
+
+    pub fn into_instances(self, profile: Option<String>) -> Vec<InstanceInfo> {
+        self.instances
+            .into_iter()
+            .map(|record| InstanceInfo::from_record(record, profile.clone()))
+            .collect()
+    }
+}
+
+/// Inventory cache, one file per profile and region, so opening a region doesn't have to
+/// wait for DescribeInstances
+pub struct Cache {}
+
+impl Cache {
+    /// Returns `None` when the region was never cached or the file can't be read
+    pub fn read(profile: &Option<String>, region: &str) -> Option<CacheEntry> {
+        Self::read_file(&Self::get_cache_path(profile, region).ok()?)
+    }
+
+    pub fn save(profile: &Option<String>, region: &str, instances: &[InstanceInfo]) -> Result<()> {
+        Self::save_file(&Self::get_cache_path(profile, region)?, instances)
+    }
+
+    fn read_file(path: &Path) -> Option<CacheEntry> {
+        let contents = std::fs::read_to_string(path).ok()?;
+        from_str(&contents).ok()
+    }
+
+    fn save_file(path: &Path, instances: &[InstanceInfo]) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let entry = CacheEntry {
+            fetched_at: get_current_time(),
+            instances: instances.iter().map(|i| i.to_record()).collect(),
+        };
+        std::fs::write(path, to_string(&entry)?)?;
+        Ok(())
+    }
+
+    fn get_cache_path(profile: &Option<String>, region: &str) -> Result<PathBuf> {
+        let Some(home_dir) = home_dir() else {
+            return Result::Err(anyhow::anyhow!("Could not find home directory"));
+        };
+        Ok(home_dir
+            .join(".sm_connect_cache")
+            .join(format!("{}.{}.json", Self::get_account_key(profile), region)))
+    }
+
+    /// Tells apart the accounts the instances come from, as far as it is known without an
+    /// STS call. Credentials in the environment take precedence over AWS_PROFILE.
+    fn get_account_key(profile: &Option<String>) -> String {
+        let key = match std::env::var("AWS_ACCESS_KEY_ID") {
+            Ok(key_id) if profile.is_none() && !key_id.is_empty() => format!("env-{}", key_id),
+            _ => get_effective_profile(profile),
+        };
+        // The name ends up in a path
+        key.chars()
+            .map(|c| match c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                true => c,
+                false => '_',
+            })
+            .collect()
+    }
+}
+
+/// Short human readable age, like "45s" or "3h"
+pub fn format_age(seconds: u64) -> String {
+    match seconds {
+        0..60 => format!("{}s", seconds),
+        60..3600 => format!("{}m", seconds / 60),
+        3600..86400 => format!("{}h", seconds / 3600),
+        _ => format!("{}d", seconds / 86400),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aws::fixture::FixtureInventory;
+
+    #[test]
+    fn round_trip() {
+        let path = std::env::temp_dir()
+            .join(format!("sm_connect_cache_test_{}", std::process::id()))
+            .join("default.eu-west-1.json");
+        let instances = FixtureInventory::test_instances();
+        Cache::save_file(&path, &instances).unwrap();
+        let entry = Cache::read_file(&path).unwrap();
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+
+        assert!(entry.get_age() < 60);
+        let cached = entry.into_instances(Some("prod".to_string()));
+        assert_eq!(cached.len(), instances.len());
+        for (cached, instance) in cached.iter().zip(&instances) {
+            assert_eq!(cached.get_profile(), Some("prod".to_string()));
+            assert_eq!(
+                serde_json::to_value(cached.to_record()).unwrap(),
+                serde_json::to_value(instance.to_record()).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn missing_file() {
+        assert!(Cache::read_file(Path::new("/nonexistent/sm_connect/cache.json")).is_none());
+    }
+
+    #[test]
+    fn account_key_is_a_file_name() {
+        let key = Cache::get_account_key(&Some("../team/admin role".to_string()));
+        assert_eq!(key, "___team_admin_role");
+    }
+
+    #[test]
+    fn age() {
+        assert_eq!(format_age(45), "45s");
+        assert_eq!(format_age(180), "3m");
+        assert_eq!(format_age(3 * 3600), "3h");
+        assert_eq!(format_age(2 * 86400), "2d");
+    }
+}
@@ -0,0 +1,399 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::Result;
+use aws_config::Region;
+use clap::{ArgGroup, Args, Parser, Subcommand};
+use thiserror::Error;
+
+use crate::app::config::{Config, SessionDocument};
+use crate::aws::{fixture::FixtureInventory, AwsInventory, InstanceInfo, InventoryProvider};
+use crate::output::Format;
+use crate::ssh_config::host_alias;
+
+/// Connect to EC2 instances through AWS Systems Manager Session Manager.
+///
+/// Without a subcommand the interactive TUI is launched.
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Cli {
+    /// AWS region to look for instances in. Falls back to AWS_DEFAULT_REGION
+    #[arg(long, global = true, env = "AWS_REGION")]
+    pub region: Option<String>,
+
+    /// AWS profile to use for fetching instances and starting sessions
+    #[arg(long, global = true)]
+    pub profile: Option<String>,
+
+    /// Send the AWS API calls to this endpoint, e.g. a LocalStack or moto server.
+    /// Takes precedence over the `endpoint_url` config key
+    #[arg(long, global = true, env = "AWS_ENDPOINT_URL")]
+    pub endpoint_url: Option<String>,
+
+    /// Read the instances from a JSON file, as written by `list --format json`,
+    /// instead of calling AWS
+    #[arg(long, global = true)]
+    pub fixture: Option<PathBuf>,
+
+    /// Always fetch instances from AWS, without reading or writing the inventory cache
+    #[arg(long)]
+    pub no_cache: bool,
+
+    /// Come back to the TUI once a session ends, as the `stay_open` config key does
+    #[arg(long)]
+    pub stay_open: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Commands>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Commands {
+    /// Connect to an instance without going through the TUI
+    Connect(ShellArgs),
+    /// Print the instances of a region
+    List(ListArgs),
+    /// Carry an ssh connection over Session Manager, as in
+    /// `ProxyCommand sm_connect --region eu-west-1 proxy %h %p`
+    Proxy(ProxyArgs),
+    /// Write a Host entry for each named instance of the region to ~/.ssh/config
+    SshConfig(SshConfigArgs),
+    /// Run ssh over Session Manager with a one-off key pushed through EC2 Instance Connect
+    Ssh(SshArgs),
+}
+
+#[derive(Debug, Args)]
+#[command(group(ArgGroup::new("target").required(true).args(["name", "id"])))]
+pub struct ConnectArgs {
+    /// Value of the instance's Name tag
+    #[arg(long)]
+    pub name: Option<String>,
+
+    /// Instance id (i-0123456789abcdef0)
+    #[arg(long)]
+    pub id: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct ShellArgs {
+    #[command(flatten)]
+    pub target: ConnectArgs,
+
+    /// SSM document to start instead of the default of the instance,
+    /// e.g. AWS-StartInteractiveCommand
+    #[arg(long)]
+    pub document_name: Option<String>,
+
+    /// Parameters of the document, as `key=value` or a JSON object,
+    /// e.g. "command=sudo -iu app"
+    #[arg(long, requires = "document_name")]
+    pub parameters: Option<String>,
+}
+
+impl ShellArgs {
+    pub fn get_document(&self) -> Result<Option<SessionDocument>> {
+        let Some(name) = &self.document_name else {
+            return Ok(None);
+        };
+        let parameters = self.parameters.as_deref().unwrap_or_default();
+        Ok(Some(SessionDocument {
+            name: name.clone(),
+            parameters: SessionDocument::parse_parameters(parameters)?,
+        }))
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct ListArgs {
+    /// Output format. The table format leaves out tags
+    #[arg(long, value_enum, default_value_t)]
+    pub format: Format,
+
+    /// Include stopped (and otherwise not running) instances
+    #[arg(long)]
+    pub all_states: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct ProxyArgs {
+    /// Instance id, Name tag or host alias written by `ssh-config`
+    pub host: String,
+
+    /// Port sshd listens on
+    #[arg(default_value_t = 22)]
+    pub port: u16,
+}
+
+#[derive(Debug, Args)]
+pub struct SshConfigArgs {
+    /// User to log in as, e.g. ec2-user
+    #[arg(long)]
+    pub user: Option<String>,
+
+    /// Prepended to the host aliases, to tell apart the instances of several profiles or
+    /// regions
+    #[arg(long, default_value = "")]
+    pub prefix: String,
+
+    /// File to write the entries to, instead of ~/.ssh/config
+    #[arg(long)]
+    pub path: Option<PathBuf>,
+
+    /// Print the entries instead of writing them
+    #[arg(long)]
+    pub print: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct SshArgs {
+    #[command(flatten)]
+    pub target: ConnectArgs,
+
+    /// OS user the key is authorized for, e.g. ubuntu on Ubuntu AMIs
+    #[arg(long, default_value = "ec2-user")]
+    pub user: String,
+
+    /// Port sshd listens on
+    #[arg(long, default_value_t = 22)]
+    pub port: u16,
+
+    /// Passed on to ssh, e.g. a command to run
+    #[arg(last = true)]
+    pub ssh_args: Vec<String>,
+}
+
+#[derive(Error, Debug)]
+pub enum CliError {
+    #[error("--region (or AWS_REGION) is required for this command")]
+    MissingRegion,
+    #[error("--name or --id is required")]
+    MissingTarget,
+    #[error("no running instance matches {0}")]
+    NoMatch(String),
+    #[error("{query} matches {} instances: {}", .matches.len(), .matches.join(", "))]
+    AmbiguousMatch { query: String, matches: Vec<String> },
+}
+
+impl Cli {
+    pub fn get_region(&self) -> Option<String> {
+        self.region
+            .clone()
+            .or_else(|| std::env::var("AWS_DEFAULT_REGION").ok())
+            .filter(|region| !region.is_empty())
+    }
+
+    /// The flag or environment variable first, then the config file
+    pub fn get_endpoint_url(&self) -> Result<Option<String>> {
+        match self.endpoint_url.clone().filter(|url| !url.is_empty()) {
+            Some(url) => Ok(Some(url)),
+            None => Ok(Config::load()?.get_endpoint_url()),
+        }
+    }
+
+    pub fn get_provider(&self) -> Result<Arc<dyn InventoryProvider>> {
+        match &self.fixture {
+            Some(path) => Ok(Arc::new(FixtureInventory::load(path)?)),
+            None => Ok(Arc::new(AwsInventory::new(self.get_endpoint_url()?))),
+        }
+    }
+
+    /// Command ssh runs to reach the instances of `region`, through this executable
+    pub fn get_proxy_command(&self, region: &Region) -> Result<String> {
+        let mut args = vec![std::env::current_exe()?.display().to_string()];
+        args.extend(["--region".to_string(), region.to_string()]);
+        if let Some(profile) = &self.profile {
+            args.extend(["--profile".to_string(), profile.clone()]);
+        }
+        if let Some(endpoint_url) = &self.endpoint_url {
+            args.extend(["--endpoint-url".to_string(), endpoint_url.clone()]);
+        }
+        if let Some(fixture) = &self.fixture {
+            args.extend(["--fixture".to_string(), fixture.display().to_string()]);
+        }
+        let mut command: Vec<String> = args.iter().map(|arg| quote_proxy_arg(arg)).collect();
+        command.extend(["proxy", "%h", "%p"].map(String::from));
+        Ok(command.join(" "))
+    }
+
+    pub fn require_region(&self) -> Result<Region> {
+        match self.get_region() {
+            Some(region) => Ok(Region::new(region)),
+            None => Err(CliError::MissingRegion.into()),
+        }
+    }
+}
+
+/// Quotes the argument for the shell ssh runs the ProxyCommand with, and escapes the `%`
+/// ssh would expand as a token
+fn quote_proxy_arg(arg: &str) -> String {
+    let safe = !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./:=@+,".contains(c));
+    let quoted = match safe {
+        true => arg.to_string(),
+        false => format!("'{}'", arg.replace('\'', "'\\''")),
+    };
+    quoted.replace('%', "%%")
+}
+
+/// Whether the host is shaped like an instance id, `i-` and 8 or 17 hex digits, rather
+/// than a name such as "i-love-web"
+fn is_instance_id(host: &str) -> bool {
+    host.strip_prefix("i-").is_some_and(|id| {
+        matches!(id.len(), 8 | 17) && id.chars().all(|c| c.is_ascii_hexdigit())
+    })
+}
+
+impl ProxyArgs {
+    pub fn get_target(&self) -> ConnectArgs {
+        match is_instance_id(&self.host) {
+            true => ConnectArgs {
+                name: None,
+                id: Some(self.host.clone()),
+            },
+            false => ConnectArgs {
+                name: Some(self.host.clone()),
+                id: None,
+            },
+        }
+    }
+}
+
+impl ConnectArgs {
+    /// Fetches the instances in the region and returns the single one matching the arguments
+    pub async fn resolve(
+        &self,
+        provider: &dyn InventoryProvider,
+        region: Region,
+        profile: Option<String>,
+    ) -> Result<InstanceInfo> {
+        let instances: Vec<InstanceInfo> = provider
+            .fetch_instances(region, profile)
+            .await?
+            .into_iter()
+            .filter(|instance| instance.is_running())
+            .collect();
+        let (query, matches): (String, Vec<InstanceInfo>) = match (&self.id, &self.name) {
+            (Some(id), _) => (
+                format!("id {}", id),
+                instances
+                    .into_iter()
+                    .filter(|instance| &instance.get_instance_id() == id)
+                    .collect(),
+            ),
+            (None, Some(name)) => (
+                format!("name {}", name),
+                instances
+                    .into_iter()
+                    .filter(|instance| {
+                        &instance.get_name() == name || &host_alias(&instance.get_name()) == name
+                    })
+                    .collect(),
+            ),
+            (None, None) => return Err(CliError::MissingTarget.into()),
+        };
+        match matches.len() {
+            0 => Err(CliError::NoMatch(query).into()),
+            1 => Ok(matches.into_iter().next().unwrap()),
+            _ => Err(CliError::AmbiguousMatch {
+                query,
+                matches: matches
+                    .iter()
+                    .map(|instance| instance.get_instance_id())
+                    .collect(),
+            }
+            .into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    fn target(name: Option<&str>, id: Option<&str>) -> ConnectArgs {
+        ConnectArgs {
+            name: name.map(String::from),
+            id: id.map(String::from),
+        }
+    }
+
+    async fn resolve(
+        provider: &FixtureInventory,
+        name: Option<&str>,
+        id: Option<&str>,
+    ) -> Result<InstanceInfo> {
+        target(name, id)
+            .resolve(provider, Region::new("eu-west-1"), None)
+            .await
+    }
+
+    #[tokio::test]
+    async fn resolve_by_id_or_name() {
+        let provider = FixtureInventory::new(FixtureInventory::test_records());
+        let instance = resolve(&provider, None, Some("i-0a1b2c3d4e5f60002")).await.unwrap();
+        assert_eq!(instance.get_name(), "web-2");
+        let instance = resolve(&provider, Some("web-1"), None).await.unwrap();
+        assert_eq!(instance.get_instance_id(), "i-0a1b2c3d4e5f60001");
+    }
+
+    #[tokio::test]
+    async fn resolve_host_alias() {
+        let mut records = FixtureInventory::test_records();
+        records[0].tags.insert("Name".to_string(), "web server".to_string());
+        let provider = FixtureInventory::new(records);
+        let instance = resolve(&provider, Some("web-server"), None).await.unwrap();
+        assert_eq!(instance.get_instance_id(), "i-0a1b2c3d4e5f60001");
+    }
+
+    #[tokio::test]
+    async fn resolve_only_running_instances_of_the_region() {
+        let provider = FixtureInventory::new(FixtureInventory::test_records());
+        for (name, id) in [(Some("batch"), None), (Some("bastion"), None), (None, Some("i-0"))] {
+            let error = resolve(&provider, name, id).await.unwrap_err();
+            assert!(matches!(error.downcast_ref(), Some(CliError::NoMatch(_))));
+        }
+    }
+
+    #[tokio::test]
+    async fn resolve_ambiguous_name() {
+        let mut records = FixtureInventory::test_records();
+        records[1].tags.insert("Name".to_string(), "web-1".to_string());
+        let provider = FixtureInventory::new(records);
+        let error = resolve(&provider, Some("web-1"), None).await.unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "name web-1 matches 2 instances: i-0a1b2c3d4e5f60001, i-0a1b2c3d4e5f60002"
+        );
+    }
+
+    #[tokio::test]
+    async fn resolve_without_target() {
+        let provider = FixtureInventory::new(FixtureInventory::test_records());
+        let error = resolve(&provider, None, None).await.unwrap_err();
+        assert!(matches!(error.downcast_ref(), Some(CliError::MissingTarget)));
+    }
+
+    #[test]
+    fn proxy_hosts_are_ids_or_names() {
+        assert!(is_instance_id("i-0a1b2c3d4e5f60001"));
+        assert!(is_instance_id("i-0a1b2c3d"));
+        assert!(!is_instance_id("i-love-web"));
+        assert!(!is_instance_id("i-deadbeef1"));
+        assert!(!is_instance_id("web-1"));
+    }
+
+    #[test]
+    fn proxy_args_are_quoted() {
+        assert_eq!(quote_proxy_arg("eu-west-1"), "eu-west-1");
+        assert_eq!(
+            quote_proxy_arg("http://localhost:4566"),
+            "http://localhost:4566"
+        );
+        assert_eq!(quote_proxy_arg("/opt/my tools/sm_connect"), "'/opt/my tools/sm_connect'");
+        assert_eq!(quote_proxy_arg("dev; rm -rf ~"), "'dev; rm -rf ~'");
+        assert_eq!(quote_proxy_arg("it's"), r#"'it'\''s'"#);
+        assert_eq!(quote_proxy_arg("50%off"), "'50%%off'");
+        assert_eq!(quote_proxy_arg(""), "''");
+    }
+}
@@ -1,15 +1,30 @@
+pub mod action_menu;
+pub mod command_results;
 pub mod config_panel;
+pub mod confirm_dialog;
+pub mod database_list;
 pub mod instance_details;
 pub mod instance_selection;
 pub mod instance_table;
+pub mod port_forward_dialog;
+pub mod profile_list;
 pub mod region_list;
+pub mod run_command_dialog;
+pub mod session_document_dialog;
+pub mod status_bar;
 pub mod text_input;
+pub mod tunnel_list;
 use config_panel::config_list::ConfigOption;
 use crossterm::event::{Event, KeyCode};
 use anyhow::Result;
 use ratatui::{layout::Rect, widgets::Widget, Frame};
 
-use crate::aws::InstanceInfo;
+use crate::app::config::{SessionDocument, Tunnel};
+use aws_config::Region;
+
+use crate::aws::{InstanceInfo, InstanceStateChange};
+use crate::session::Session;
+use status_bar::Level;
 
 pub enum Action {
     Noop,
@@ -26,6 +41,29 @@ pub enum Action {
     Hide(String),
     Reset,
     ToggleFavorite(String),
+    Confirm,
+    OpenAllRegions,
+    OpenInstanceActions(InstanceInfo),
+    ChangeInstanceState(InstanceInfo, InstanceStateChange),
+    OpenPortForward(InstanceInfo),
+    StartSession(Session),
+    OpenTunnels,
+    LaunchTunnel(Tunnel),
+    DeleteTunnel(Tunnel),
+    /// Asks for a command to run on the instances
+    OpenRunCommand(Vec<InstanceInfo>),
+    RunCommand(Vec<InstanceInfo>, String),
+    /// Asks for the SSM document a shell to the instance should start
+    OpenSessionDocument(InstanceInfo),
+    /// Sets the default document of an instance id or `Key=Value` tag, `None` for the
+    /// default shell
+    SaveSessionDocument(String, Option<SessionDocument>),
+    /// Lists the RDS and Aurora endpoints of the region
+    OpenDatabases(Region),
+    Refresh,
+    Cancel,
+    /// Message for the status bar
+    Notify(Level, String),
 }
 
 pub trait HandleAction {
@@ -0,0 +1,135 @@
+use crossterm::event::{Event, KeyCode};
+use ratatui::{
+    layout::{Constraint, Flex, Layout, Rect},
+    style::{Color, Modifier, Style},
+    widgets::{Block, Borders, Clear, List, ListItem, ListState},
+    Frame,
+};
+
+use super::{Action, HandleAction, Render, View};
+use crate::aws::{InstanceInfo, InstanceStateChange};
+use anyhow::Result;
+
+/// Popup listing the state changes available for an instance
+#[derive(Debug, Clone)]
+pub struct ActionMenu {
+    state: ListState,
+    instance: InstanceInfo,
+    items: Vec<InstanceStateChange>,
+}
+
+impl ActionMenu {
+    pub fn new(instance: InstanceInfo) -> ActionMenu {
+        let mut state = ListState::default();
+        state.select(Some(0));
+        let items = InstanceStateChange::available_for(&instance.get_state());
+        ActionMenu {
+            state,
+            instance,
+            items,
+        }
+    }
+
+    fn next(&mut self) {
+        let i = match self.state.selected() {
+            Some(i) => {
+                if i >= self.items.len().saturating_sub(1) {
+                    0
+                } else {
+                    i + 1
+                }
+            }
+            None => 0,
+        };
+        self.state.select(Some(i));
+    }
+
+    fn previous(&mut self) {
+        let i = match self.state.selected() {
+            Some(i) => {
+                if i == 0 {
+                    self.items.len().saturating_sub(1)
+                } else {
+                    i - 1
+                }
+            }
+            None => 0,
+        };
+        self.state.select(Some(i));
+    }
+
+    fn current(&self) -> Option<InstanceStateChange> {
+        self.state.selected().and_then(|i| self.items.get(i).copied())
+    }
+}
+
+impl HandleAction for ActionMenu {
+    fn handle_action(&mut self, action: Event) -> Result<Action> {
+        let action = match action {
+            Event::Key(key) => match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => Action::Exit,
+                KeyCode::Down => {
+                    self.next();
+                    Action::Noop
+                }
+                KeyCode::Up => {
+                    self.previous();
+                    Action::Noop
+                }
+                KeyCode::Right | KeyCode::Enter => match self.current() {
+                    Some(change) => Action::ChangeInstanceState(self.instance.clone(), change),
+                    None => Action::Noop,
+                },
+                _ => Action::Noop,
+            },
+            _ => Action::Noop,
+        };
+        Ok(action)
+    }
+}
+
+#[allow(refining_impl_trait)]
+impl View for ActionMenu {
+    fn get_widget(&self) -> List<'_> {
+        let items: Vec<ListItem> = if self.items.is_empty() {
+            vec![ListItem::new(format!(
+                "Nothing to do while {}",
+                self.instance.get_state()
+            ))]
+        } else {
+            self.items
+                .iter()
+                .map(|i| {
+                    let name: String = (*i).into();
+                    ListItem::new(name)
+                })
+                .collect()
+        };
+        List::new(items)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(format!(" {} ", self.instance.get_name())),
+            )
+            .highlight_style(
+                Style::default()
+                    .bg(Color::LightGreen)
+                    .add_modifier(Modifier::BOLD),
+            )
+            .highlight_symbol(">> ")
+    }
+}
+
+impl Render for ActionMenu {
+    fn render(&mut self, frame: &mut Frame, area: Rect) {
+        let [area] = Layout::vertical([Constraint::Length(6)])
+            .flex(Flex::Center)
+            .areas(area);
+        let [area] = Layout::horizontal([Constraint::Length(40)])
+            .flex(Flex::Center)
+            .areas(area);
+        frame.render_widget(Clear, area);
+        let widget = self.get_widget();
+        frame.render_stateful_widget(widget, area, &mut self.state.clone());
+    }
+}
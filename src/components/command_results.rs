@@ -0,0 +1,221 @@
+use super::{Action, HandleAction, Render, RenderHelp, View};
+use crate::aws::run_command::CommandInvocation;
+use crate::aws::InstanceInfo;
+use crossterm::event::{Event, KeyCode};
+use ratatui::{
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style, Stylize},
+    text::{Line, Span, Text},
+    widgets::{Block, Borders, Cell, Paragraph, Row, Table, TableState, Wrap},
+    Frame,
+};
+use anyhow::Result;
+
+/// Status and output of a Run Command, one row per instance
+#[derive(Default, Debug, Clone)]
+pub struct CommandResults {
+    state: TableState,
+    command: String,
+    items: Vec<(InstanceInfo, CommandInvocation)>,
+    /// Lines scrolled down in the output of the selected instance
+    scroll: u16,
+}
+
+impl CommandResults {
+    pub fn start(&mut self, command: String, instances: Vec<InstanceInfo>) {
+        self.command = command;
+        self.items = instances
+            .into_iter()
+            .map(|instance| (instance, CommandInvocation::pending()))
+            .collect();
+        self.state.select(Some(0));
+        self.scroll = 0;
+    }
+
+    /// Updates the row of the instance, a failure to send or poll counts as a failed invocation
+    pub fn update(&mut self, instance_id: &str, result: Result<CommandInvocation>) {
+        let invocation = result.unwrap_or_else(|e| CommandInvocation {
+            status: "Error".to_string(),
+            stdout: String::new(),
+            stderr: format!("{:#}", e),
+        });
+        if let Some((_, current)) = self
+            .items
+            .iter_mut()
+            .find(|(instance, _)| instance.get_instance_id() == instance_id)
+        {
+            *current = invocation;
+        }
+    }
+
+    fn get_finished(&self) -> usize {
+        self.items
+            .iter()
+            .filter(|(_, invocation)| invocation.is_finished() || invocation.status == "Error")
+            .count()
+    }
+
+    fn select(&mut self, i: usize) {
+        self.state.select(Some(i));
+        self.scroll = 0;
+    }
+
+    fn next(&mut self) {
+        if self.items.is_empty() {
+            return;
+        }
+        match self.state.selected() {
+            Some(i) if i + 1 < self.items.len() => self.select(i + 1),
+            _ => self.select(0),
+        }
+    }
+
+    fn previous(&mut self) {
+        if self.items.is_empty() {
+            return;
+        }
+        match self.state.selected() {
+            Some(0) | None => self.select(self.items.len() - 1),
+            Some(i) => self.select(i - 1),
+        }
+    }
+
+    fn render_output(&self, frame: &mut Frame, area: Rect) {
+        let Some((instance, invocation)) = self.state.selected().and_then(|i| self.items.get(i))
+        else {
+            return;
+        };
+        let mut text = Text::default();
+        for line in invocation.stdout.lines() {
+            text.push_line(Line::from(line.to_string()));
+        }
+        for line in invocation.stderr.lines() {
+            text.push_line(Line::styled(line.to_string(), Style::default().fg(Color::Red)));
+        }
+        let widget = Paragraph::new(text)
+            .wrap(Wrap { trim: false })
+            .scroll((self.scroll, 0))
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(format!(" Output of {} ", instance.get_name())),
+            );
+        frame.render_widget(widget, area);
+    }
+}
+
+impl HandleAction for CommandResults {
+    fn handle_action(&mut self, action: Event) -> Result<Action> {
+        let action = match action {
+            Event::Key(key) => match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => Action::Exit,
+                KeyCode::Down => {
+                    self.next();
+                    Action::Noop
+                }
+                KeyCode::Up => {
+                    self.previous();
+                    Action::Noop
+                }
+                KeyCode::PageDown => {
+                    self.scroll = self.scroll.saturating_add(10);
+                    Action::Noop
+                }
+                KeyCode::PageUp => {
+                    self.scroll = self.scroll.saturating_sub(10);
+                    Action::Noop
+                }
+                _ => Action::Noop,
+            },
+            _ => Action::Noop,
+        };
+        Ok(action)
+    }
+}
+
+#[allow(refining_impl_trait)]
+impl View for CommandResults {
+    fn get_widget(&self) -> Table<'_> {
+        let items: Vec<Row> = self
+            .items
+            .iter()
+            .map(|(instance, invocation)| {
+                let color = match invocation.status.as_str() {
+                    "Success" => Color::Green,
+                    "Pending" | "InProgress" | "Delayed" => Color::Yellow,
+                    "Skipped" => Color::DarkGray,
+                    _ => Color::Red,
+                };
+                Row::new(vec![
+                    Cell::from(instance.get_name()),
+                    Cell::from(instance.get_instance_id()),
+                    Cell::from(instance.get_region().to_string()),
+                    Cell::from(invocation.status.clone()).style(Style::default().fg(color)),
+                ])
+            })
+            .collect();
+        let widths = [
+            Constraint::Ratio(1, 3),
+            Constraint::Ratio(1, 4),
+            Constraint::Ratio(1, 6),
+            Constraint::Ratio(1, 4),
+        ];
+        let title = format!(
+            " `{}` {}/{} done ",
+            self.command,
+            self.get_finished(),
+            self.items.len()
+        );
+        Table::new(items, widths)
+            .block(Block::default().borders(Borders::ALL).title(title))
+            .row_highlight_style(
+                Style::default()
+                    .bg(Color::LightGreen)
+                    .add_modifier(Modifier::BOLD),
+            )
+            .highlight_symbol(">> ")
+            .header(
+                Row::new(vec!["Name", "InstanceId", "Region", "Status"])
+                    .style(Style::default().add_modifier(Modifier::BOLD).underlined()),
+            )
+    }
+}
+
+impl Render for CommandResults {
+    fn render(&mut self, frame: &mut Frame, area: Rect) {
+        let vertical_layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(vec![
+                Constraint::Percentage(40),
+                Constraint::Percentage(50),
+                Constraint::Percentage(10),
+            ])
+            .split(area);
+
+        let widget = self.get_widget();
+        frame.render_stateful_widget(widget, vertical_layout[0], &mut self.state.clone());
+        self.render_output(frame, vertical_layout[1]);
+        self.render_help(frame, vertical_layout[2]);
+    }
+}
+
+impl RenderHelp for CommandResults {
+    fn render_help(&mut self, frame: &mut Frame, area: Rect) {
+        let rows = vec![Row::new(vec![
+            Cell::from(Span::styled("'q' Back", Style::default().fg(Color::White))),
+            Cell::from(Span::styled(
+                "'Up'/'Down' Instance",
+                Style::default().fg(Color::White),
+            )),
+            Cell::from(Span::styled(
+                "'PgUp'/'PgDn' Scroll output",
+                Style::default().fg(Color::White),
+            )),
+        ])];
+        let table = Table::new(
+            rows,
+            vec![Constraint::Min(10), Constraint::Min(10), Constraint::Min(10)],
+        );
+        frame.render_widget(table, area);
+    }
+}
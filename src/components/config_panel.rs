@@ -4,6 +4,7 @@ use crate::{
     app::config::Config,
     components::text_input::TextInput,
     history::History,
+    session::split_command_template,
 };
 use config_list::{ConfigList, ConfigOption};
 use ratatui::{
@@ -13,7 +14,7 @@ use ratatui::{
     Frame,
 };
 
-use super::{Action, HandleAction, Render, RenderHelp};
+use super::{status_bar::Level, Action, HandleAction, Render, RenderHelp};
 pub mod config_list;
 use anyhow::Result;
 #[derive(Debug)]
@@ -50,16 +51,34 @@ impl HandleAction for ConfigPanel {
                     self.input_active = false;
                 }
                 Action::Return(search) => {
-                    if let Some(ConfigOption::SetRecentTimeout) = self.modifying_action {
-                        if let Ok(timeout) = search.parse::<u64>() {
+                    match (self.modifying_action, search.parse::<u64>()) {
+                        // Empty resets to `aws ssm start-session`
+                        (Some(ConfigOption::SetSessionCommand), _) => {
+                            let valid = search.trim().is_empty()
+                                || split_command_template(&search).is_ok();
+                            if valid {
+                                self.config
+                                    .lock()
+                                    .unwrap()
+                                    .set_session_command(search)?;
+                            }
+                            self.last_operation_success = Some(valid);
+                        }
+                        (Some(ConfigOption::SetRecentTimeout), Ok(timeout)) => {
                             self.config
                                 .lock()
                                 .unwrap()
                                 .set_recent_timeout(timeout)?;
                             self.last_operation_success = Some(true);
-                        } else {
+                        }
+                        (Some(ConfigOption::SetCacheTtl), Ok(ttl)) => {
+                            self.config.lock().unwrap().set_cache_ttl(ttl)?;
+                            self.last_operation_success = Some(true);
+                        }
+                        (Some(_), Err(_)) => {
                             self.last_operation_success = Some(false);
                         }
+                        _ => {}
                     }
                     self.input_active = false;
                 }
@@ -76,8 +95,11 @@ impl HandleAction for ConfigPanel {
                                 self.last_operation_success = Some(true);
                             }
                             Err(e) => {
-                                eprintln!("Error resetting history: {:?}", e);
                                 self.last_operation_success = Some(false);
+                                return Ok(Action::Notify(
+                                    Level::Error,
+                                    format!("Error resetting history: {:#}", e),
+                                ));
                             }
                         },
                         ConfigOption::SetRecentTimeout => {
@@ -86,6 +108,38 @@ impl HandleAction for ConfigPanel {
                             let current_value = self.config.lock().unwrap().get_recent_timeout();
                             self.input_component.set_value(current_value.to_string());
                         }
+                        ConfigOption::SetCacheTtl => {
+                            self.modifying_action = Some(ConfigOption::SetCacheTtl);
+                            self.input_active = true;
+                            let current_value = self.config.lock().unwrap().get_cache_ttl();
+                            self.input_component.set_value(current_value.to_string());
+                        }
+                        ConfigOption::SetSessionCommand => {
+                            self.modifying_action = Some(ConfigOption::SetSessionCommand);
+                            self.input_active = true;
+                            let current_value = self.config.lock().unwrap().get_session_command();
+                            self.input_component
+                                .set_value(current_value.unwrap_or_default());
+                        }
+                        ConfigOption::CycleTmuxLayout => {
+                            let mut config = self.config.lock().unwrap();
+                            let layout = config.get_tmux_layout().next();
+                            config.set_tmux_layout(layout)?;
+                            let layout: String = layout.into();
+                            return Ok(Action::Notify(
+                                Level::Info,
+                                format!("Sessions open in tmux with: {}", layout),
+                            ));
+                        }
+                        ConfigOption::ToggleStayOpen => {
+                            let mut config = self.config.lock().unwrap();
+                            config.toggle_stay_open()?;
+                            let message = match config.get_stay_open() {
+                                true => "The TUI now comes back once a session ends",
+                                false => "sm_connect now exits once a session starts",
+                            };
+                            return Ok(Action::Notify(Level::Info, message.to_string()));
+                        }
                     }
                     Ok(Action::Noop)
                 }
@@ -12,6 +12,10 @@ use anyhow::Result;
 pub enum ConfigOption {
     ResetRecent,
     SetRecentTimeout,
+    SetCacheTtl,
+    CycleTmuxLayout,
+    ToggleStayOpen,
+    SetSessionCommand,
 }
 
 impl From<ConfigOption> for String {
@@ -19,12 +23,22 @@ impl From<ConfigOption> for String {
         match option {
             ConfigOption::ResetRecent => "Reset Recent Instances".to_string(),
             ConfigOption::SetRecentTimeout => "Set Recent Timeout".to_string(),
+            ConfigOption::SetCacheTtl => "Set Cache TTL (seconds)".to_string(),
+            ConfigOption::CycleTmuxLayout => "Change tmux Layout".to_string(),
+            ConfigOption::ToggleStayOpen => "Toggle Stay Open After Sessions".to_string(),
+            ConfigOption::SetSessionCommand => "Set Session Command".to_string(),
         }
     }
 }
 
-const CONFIG_OPTIONS: [ConfigOption; 2] =
-    [ConfigOption::ResetRecent, ConfigOption::SetRecentTimeout];
+const CONFIG_OPTIONS: [ConfigOption; 6] = [
+    ConfigOption::ResetRecent,
+    ConfigOption::SetRecentTimeout,
+    ConfigOption::SetCacheTtl,
+    ConfigOption::CycleTmuxLayout,
+    ConfigOption::ToggleStayOpen,
+    ConfigOption::SetSessionCommand,
+];
 #[derive(Debug)]
 pub struct ConfigList {
     state: ListState,
@@ -96,7 +110,7 @@ impl HandleAction for ConfigList {
 
 #[allow(refining_impl_trait)]
 impl View for ConfigList {
-    fn get_widget(&self) -> List {
+    fn get_widget(&self) -> List<'_> {
         let items: Vec<ListItem> = CONFIG_OPTIONS
             .iter()
             .map(|i| {
@@ -0,0 +1,83 @@
+use crossterm::event::{Event, KeyCode};
+use ratatui::{
+    layout::{Constraint, Flex, Layout, Rect},
+    style::{Color, Style},
+    text::{Line, Text},
+    widgets::{Block, Borders, Clear, Paragraph, Wrap},
+    Frame,
+};
+
+use super::{Action, HandleAction, Render, View};
+use anyhow::Result;
+
+/// Yes/no question rendered as a popup on top of the current screen
+#[derive(Debug, Clone)]
+pub struct ConfirmDialog {
+    title: String,
+    message: String,
+    footer: String,
+}
+
+impl ConfirmDialog {
+    pub fn new(title: String, message: String) -> ConfirmDialog {
+        ConfirmDialog {
+            title,
+            message,
+            footer: "'y' Yes    'n' No".to_string(),
+        }
+    }
+
+    /// Replaces the yes/no hint, for popups only waiting on something
+    pub fn with_footer(mut self, footer: String) -> ConfirmDialog {
+        self.footer = footer;
+        self
+    }
+
+    pub fn set_message(&mut self, message: String) {
+        self.message = message;
+    }
+}
+
+impl HandleAction for ConfirmDialog {
+    fn handle_action(&mut self, action: Event) -> Result<Action> {
+        let action = match action {
+            Event::Key(key) => match key.code {
+                KeyCode::Char('y') | KeyCode::Enter => Action::Confirm,
+                KeyCode::Char('n') | KeyCode::Char('q') | KeyCode::Esc => Action::Exit,
+                _ => Action::Noop,
+            },
+            _ => Action::Noop,
+        };
+        Ok(action)
+    }
+}
+
+#[allow(refining_impl_trait)]
+impl View for ConfirmDialog {
+    fn get_widget(&self) -> Paragraph<'_> {
+        let mut text = Text::from(self.message.as_str());
+        text.push_line(Line::default());
+        text.push_line(Line::from(self.footer.as_str()).centered());
+        Paragraph::new(text)
+            .wrap(Wrap { trim: false })
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(self.title.as_str())
+                    .border_style(Style::default().fg(Color::Yellow)),
+            )
+    }
+}
+
+impl Render for ConfirmDialog {
+    fn render(&mut self, frame: &mut Frame, area: Rect) {
+        let [area] = Layout::vertical([Constraint::Length(7)])
+            .flex(Flex::Center)
+            .areas(area);
+        let [area] = Layout::horizontal([Constraint::Percentage(60)])
+            .flex(Flex::Center)
+            .areas(area);
+        frame.render_widget(Clear, area);
+        frame.render_widget(self.get_widget(), area);
+    }
+}
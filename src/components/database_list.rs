@@ -0,0 +1,210 @@
+use super::status_bar::Level;
+use super::{Action, HandleAction, Render, RenderHelp, View};
+use crate::aws::rds::DatabaseInfo;
+use crate::aws::InstanceInfo;
+use crate::session::{free_local_port, Session};
+use crossterm::event::{Event, KeyCode};
+use ratatui::{
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style, Stylize},
+    text::Span,
+    widgets::{Block, Borders, Cell, Row, Table, TableState},
+    Frame,
+};
+use anyhow::Result;
+
+/// RDS and Aurora endpoints of a region, each with the instance to tunnel through
+#[derive(Default, Debug, Clone)]
+pub struct DatabaseList {
+    state: TableState,
+    region: String,
+    items: Vec<(DatabaseInfo, Option<InstanceInfo>)>,
+    loading: bool,
+}
+
+impl DatabaseList {
+    /// Empties the list while the databases of `region` are fetched
+    pub fn start_loading(&mut self, region: String) {
+        self.region = region;
+        self.items.clear();
+        self.loading = true;
+        self.state.select(Some(0));
+    }
+
+    /// Shows the databases, suggesting a bastion for each among `instances`
+    pub fn set_items(&mut self, databases: Vec<DatabaseInfo>, instances: &[InstanceInfo]) {
+        self.loading = false;
+        self.items = databases
+            .into_iter()
+            .map(|database| {
+                let bastion = suggest_bastion(&database, instances);
+                (database, bastion)
+            })
+            .collect();
+        self.state.select(Some(0));
+    }
+
+    pub fn cancel_loading(&mut self) {
+        self.loading = false;
+    }
+
+    fn next(&mut self) {
+        if self.items.is_empty() {
+            return;
+        }
+        let i = match self.state.selected() {
+            Some(i) if i + 1 < self.items.len() => i + 1,
+            _ => 0,
+        };
+        self.state.select(Some(i));
+    }
+
+    fn previous(&mut self) {
+        if self.items.is_empty() {
+            return;
+        }
+        let i = match self.state.selected() {
+            Some(0) | None => self.items.len() - 1,
+            Some(i) => i - 1,
+        };
+        self.state.select(Some(i));
+    }
+
+    /// Forwards a free local port, preferably the database's own, through the bastion
+    fn connect(&self) -> Action {
+        let Some((database, bastion)) = self.state.selected().and_then(|i| self.items.get(i))
+        else {
+            return Action::Noop;
+        };
+        let Some(bastion) = bastion else {
+            return Action::Notify(
+                Level::Warning,
+                format!(
+                    "No running instance in {} to tunnel to {} through",
+                    database.vpc_id, database.identifier
+                ),
+            );
+        };
+        Action::StartSession(Session::PortForward {
+            instance: bastion.clone(),
+            remote_host: Some(database.endpoint.clone()),
+            remote_port: database.port,
+            local_port: free_local_port(database.port),
+        })
+    }
+}
+
+/// A running instance of the database's region and VPC, preferably online in Session Manager
+fn suggest_bastion(database: &DatabaseInfo, instances: &[InstanceInfo]) -> Option<InstanceInfo> {
+    let candidates: Vec<&InstanceInfo> = instances
+        .iter()
+        .filter(|instance| {
+            instance.is_running()
+                && instance.get_region() == database.region
+                && !database.vpc_id.is_empty()
+                && instance.get_vpc_id() == database.vpc_id
+        })
+        .collect();
+    candidates
+        .iter()
+        .find(|instance| instance.is_ssm_online())
+        .or(candidates.first())
+        .map(|instance| (*instance).clone())
+}
+
+impl HandleAction for DatabaseList {
+    fn handle_action(&mut self, action: Event) -> Result<Action> {
+        let action = match action {
+            Event::Key(key) => match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => Action::Exit,
+                KeyCode::Down => {
+                    self.next();
+                    Action::Noop
+                }
+                KeyCode::Up => {
+                    self.previous();
+                    Action::Noop
+                }
+                KeyCode::Right | KeyCode::Enter => self.connect(),
+                _ => Action::Noop,
+            },
+            _ => Action::Noop,
+        };
+        Ok(action)
+    }
+}
+
+#[allow(refining_impl_trait)]
+impl View for DatabaseList {
+    fn get_widget(&self) -> Table<'_> {
+        let items: Vec<Row> = self
+            .items
+            .iter()
+            .map(|(database, bastion)| {
+                let bastion = match bastion {
+                    Some(instance) => Cell::from(instance.get_name()),
+                    None => Cell::from("none in VPC").style(Style::default().fg(Color::Red)),
+                };
+                Row::new(vec![
+                    Cell::from(format!("{} ({})", database.identifier, database.kind)),
+                    Cell::from(database.engine.clone()),
+                    Cell::from(database.endpoint.clone()),
+                    Cell::from(database.port.to_string()),
+                    Cell::from(database.status.clone()),
+                    bastion,
+                ])
+            })
+            .collect();
+        let widths = [
+            Constraint::Ratio(1, 5),
+            Constraint::Ratio(1, 10),
+            Constraint::Ratio(2, 5),
+            Constraint::Length(6),
+            Constraint::Ratio(1, 10),
+            Constraint::Ratio(1, 6),
+        ];
+        let title = match self.loading {
+            true => format!(" Databases in {} (loading) ", self.region),
+            false => format!(" Databases in {} ", self.region),
+        };
+        Table::new(items, widths)
+            .block(Block::default().borders(Borders::ALL).title(title))
+            .row_highlight_style(
+                Style::default()
+                    .bg(Color::LightGreen)
+                    .add_modifier(Modifier::BOLD),
+            )
+            .highlight_symbol(">> ")
+            .header(
+                Row::new(vec!["Identifier", "Engine", "Endpoint", "Port", "Status", "Bastion"])
+                    .style(Style::default().add_modifier(Modifier::BOLD).underlined()),
+            )
+    }
+}
+
+impl Render for DatabaseList {
+    fn render(&mut self, frame: &mut Frame, area: Rect) {
+        let vertical_layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(vec![Constraint::Percentage(90), Constraint::Percentage(10)])
+            .split(area);
+
+        let widget = self.get_widget();
+        frame.render_stateful_widget(widget, vertical_layout[0], &mut self.state.clone());
+        self.render_help(frame, vertical_layout[1]);
+    }
+}
+
+impl RenderHelp for DatabaseList {
+    fn render_help(&mut self, frame: &mut Frame, area: Rect) {
+        let rows = vec![Row::new(vec![
+            Cell::from(Span::styled("'q' Back", Style::default().fg(Color::White))),
+            Cell::from(Span::styled(
+                "'Enter' Tunnel through the bastion",
+                Style::default().fg(Color::White),
+            )),
+        ])];
+        let table = Table::new(rows, vec![Constraint::Min(10), Constraint::Min(10)]);
+        frame.render_widget(table, area);
+    }
+}
@@ -19,9 +19,10 @@ impl InstanceDetails {
 
 #[allow(refining_impl_trait)]
 impl View for InstanceDetails {
-    fn get_widget(&self) -> Paragraph {
+    fn get_widget(&self) -> Paragraph<'_> {
         let text = match &self.instance {
             Some(instance) => {
+                let node = instance.get_managed_node().unwrap_or_default();
                 let data = vec![
                     ("Name", instance.get_name()),
                     ("Instance Id", instance.get_instance_id()),
@@ -35,6 +36,10 @@ impl View for InstanceDetails {
                         "security_groups",
                         format!("{:#?}", instance.get_security_groups()),
                     ),
+                    ("ssm_status", instance.get_ssm_status()),
+                    ("ssm_agent_version", node.agent_version),
+                    ("platform", node.platform_name),
+                    ("ssm_last_ping", node.last_ping),
                     ("tags", format!("{:#?}", instance.get_tags())),
                 ];
                 let text = data
@@ -1,16 +1,37 @@
 use crossterm::event;
 use ratatui::{
     layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Style},
+    text::{Line, Text},
+    widgets::{Block, Borders, Paragraph},
     Frame,
 };
 
 use crate::{
-    aws::InstanceInfo,
-    components::{instance_table::InstanceTable, text_input::TextInput},
+    app::config::DocumentRule,
+    aws::{InstanceInfo, InstanceStateChange},
+    components::{
+        action_menu::ActionMenu, confirm_dialog::ConfirmDialog, instance_table::InstanceTable,
+        port_forward_dialog::PortForwardDialog, run_command_dialog::RunCommandDialog,
+        session_document_dialog::SessionDocumentDialog, text_input::TextInput,
+    },
 };
 
 use super::{instance_details::InstanceDetails, Action, HandleAction, Render, RenderHelp};
 use anyhow::Result;
+
+const SPINNER: [&str; 10] = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+
+/// Instances being fetched, one region at a time
+#[derive(Debug, Clone, Default)]
+struct LoadProgress {
+    total: usize,
+    loaded: usize,
+    /// Set when refreshing: the instances are swapped in once every region answered,
+    /// so the filter and selection survive
+    refreshed: Option<Vec<InstanceInfo>>,
+}
+
 #[derive(Debug, Clone)]
 pub struct InstanceSelection {
     instances_table_component: InstanceTable,
@@ -18,6 +39,18 @@ pub struct InstanceSelection {
     instance_details: InstanceDetails,
     search_active: bool,
     info_panel_enabled: bool,
+    load_progress: Option<LoadProgress>,
+    /// Whether instances come from several regions, as in the "All regions" view
+    multi_region: bool,
+    region_failures: Vec<(String, String)>,
+    spinner_frame: usize,
+    action_menu: Option<ActionMenu>,
+    pending_state_change: Option<(ConfirmDialog, InstanceInfo, InstanceStateChange)>,
+    port_forward: Option<PortForwardDialog>,
+    run_command: Option<RunCommandDialog>,
+    session_document: Option<SessionDocumentDialog>,
+    /// Remote hosts of the saved tunnels, suggested when port forwarding
+    known_hosts: Vec<String>,
 }
 
 impl Default for InstanceSelection {
@@ -38,20 +71,221 @@ impl InstanceSelection {
             instance_details,
             search_active: false,
             info_panel_enabled: false,
+            load_progress: None,
+            multi_region: false,
+            region_failures: Vec::new(),
+            spinner_frame: 0,
+            action_menu: None,
+            pending_state_change: None,
+            port_forward: None,
+            run_command: None,
+            session_document: None,
+            known_hosts: Vec::new(),
+        }
+    }
+
+    pub fn replace_instance(&mut self, instance: InstanceInfo) {
+        self.instances_table_component.replace_item(instance.clone());
+        if let Some(current) = self.instances_table_component.current()
+            && current.get_instance_id() == instance.get_instance_id()
+        {
+            self.instance_details.set_instance(instance);
         }
     }
 
-    pub fn update_instances(&mut self, instances: Vec<InstanceInfo>) {
-        self.instances_table_component =
-            InstanceTable::with_items_and_filter(instances, self.search_component.get_value());
+    pub fn get_instances(&self) -> &[InstanceInfo] {
+        self.instances_table_component.get_items()
+    }
+
+    /// Asks for the document of a shell to the instance, starting from the rule giving its
+    /// default one
+    pub fn open_session_document(&mut self, instance: InstanceInfo, default: Option<DocumentRule>) {
+        self.session_document = Some(SessionDocumentDialog::new(instance, default));
+    }
+
+    pub fn set_known_hosts(&mut self, known_hosts: Vec<String>) {
+        self.known_hosts = known_hosts;
+    }
+
+    /// Prepares for the instances of `regions` regions to come in. Unless refreshing,
+    /// the table is emptied and filled as each region answers.
+    pub fn start_loading(&mut self, regions: usize, multi_region: bool, refresh: bool) {
+        if !refresh {
+            self.instances_table_component.set_items(vec![]);
+            self.instances_table_component.set_cache_note(None);
+        }
+        self.multi_region = multi_region;
+        self.instances_table_component.set_show_region(multi_region);
+        self.region_failures.clear();
+        self.load_progress = Some(LoadProgress {
+            total: regions,
+            refreshed: refresh.then(Vec::new),
+            ..Default::default()
+        });
+        self.tick();
+    }
+
+    /// Shows instances read from the cache, `note` telling how old they are
+    pub fn show_cached(&mut self, instances: Vec<InstanceInfo>, multi_region: bool, note: String) {
+        self.multi_region = multi_region;
+        self.instances_table_component.set_show_region(multi_region);
+        self.region_failures.clear();
+        self.instances_table_component.set_items(instances);
+        self.instances_table_component.set_cache_note(Some(note));
         if let Some(instance) = self.instances_table_component.current() {
             self.instance_details.set_instance(instance);
         }
     }
+
+    pub fn add_region_result(&mut self, region: String, result: Result<Vec<InstanceInfo>>) {
+        let Some(progress) = &mut self.load_progress else {
+            return;
+        };
+        progress.loaded += 1;
+        match result {
+            Ok(instances) => match &mut progress.refreshed {
+                Some(refreshed) => refreshed.extend(instances),
+                None => {
+                    self.instances_table_component.append_items(instances);
+                    if let Some(instance) = self.instances_table_component.current() {
+                        self.instance_details.set_instance(instance);
+                    }
+                }
+            },
+            Err(e) => self.region_failures.push((region, e.to_string())),
+        }
+    }
+
+    pub fn finish_loading(&mut self) {
+        if let Some(LoadProgress {
+            refreshed: Some(instances),
+            ..
+        }) = self.load_progress.take()
+        {
+            self.instances_table_component.set_items(instances);
+            self.instances_table_component.set_cache_note(None);
+            if let Some(instance) = self.instances_table_component.current() {
+                self.instance_details.set_instance(instance);
+            }
+        }
+        self.instances_table_component.set_loading_indicator(None);
+    }
+
+    /// Stops waiting for the remaining regions, keeping what was already shown
+    pub fn cancel_loading(&mut self) {
+        self.load_progress = None;
+        self.instances_table_component.set_loading_indicator(None);
+    }
+
+    /// Advances the loading spinner
+    pub fn tick(&mut self) {
+        if self.load_progress.is_none() {
+            return;
+        }
+        self.spinner_frame = (self.spinner_frame + 1) % SPINNER.len();
+        self.instances_table_component
+            .set_loading_indicator(Some(format!("{} Loading", SPINNER[self.spinner_frame])));
+    }
+
+    fn render_region_progress(&self, frame: &mut Frame, area: Rect) {
+        let summary = match &self.load_progress {
+            Some(progress) => format!(
+                "Loaded {}/{} regions, {} failed",
+                progress.loaded,
+                progress.total,
+                self.region_failures.len()
+            ),
+            None => format!("{} regions failed", self.region_failures.len()),
+        };
+        let mut text = Text::from(summary);
+        for (region, error) in &self.region_failures {
+            text.push_line(Line::styled(
+                format!("{}: {}", region, error),
+                Style::default().fg(Color::Red),
+            ));
+        }
+        let widget =
+            Paragraph::new(text).block(Block::default().borders(Borders::ALL).title("Regions"));
+        frame.render_widget(widget, area);
+    }
 }
 
 impl HandleAction for InstanceSelection {
     fn handle_action(&mut self, action: crossterm::event::Event) -> Result<Action> {
+        if let Some((dialog, instance, change)) = &mut self.pending_state_change {
+            return match dialog.handle_action(action)? {
+                Action::Confirm => {
+                    let action = Action::ChangeInstanceState(instance.clone(), *change);
+                    self.pending_state_change = None;
+                    Ok(action)
+                }
+                Action::Exit => {
+                    self.pending_state_change = None;
+                    Ok(Action::Noop)
+                }
+                _ => Ok(Action::Noop),
+            };
+        }
+        if let Some(dialog) = &mut self.port_forward {
+            return match dialog.handle_action(action)? {
+                Action::Exit => {
+                    self.port_forward = None;
+                    Ok(Action::Noop)
+                }
+                Action::StartSession(session) => {
+                    self.port_forward = None;
+                    Ok(Action::StartSession(session))
+                }
+                _ => Ok(Action::Noop),
+            };
+        }
+        if let Some(dialog) = &mut self.run_command {
+            return match dialog.handle_action(action)? {
+                Action::Exit => {
+                    self.run_command = None;
+                    Ok(Action::Noop)
+                }
+                Action::RunCommand(instances, command) => {
+                    self.run_command = None;
+                    Ok(Action::RunCommand(instances, command))
+                }
+                _ => Ok(Action::Noop),
+            };
+        }
+        if let Some(dialog) = &mut self.session_document {
+            return match dialog.handle_action(action)? {
+                Action::Exit => {
+                    self.session_document = None;
+                    Ok(Action::Noop)
+                }
+                action @ (Action::StartSession(_) | Action::SaveSessionDocument(_, _)) => {
+                    self.session_document = None;
+                    Ok(action)
+                }
+                _ => Ok(Action::Noop),
+            };
+        }
+        if let Some(menu) = &mut self.action_menu {
+            match menu.handle_action(action)? {
+                Action::Exit => {
+                    self.action_menu = None;
+                }
+                Action::ChangeInstanceState(instance, change) => {
+                    self.action_menu = None;
+                    let verb: String = change.into();
+                    let message = format!(
+                        "{} {} ({})?",
+                        verb,
+                        instance.get_name(),
+                        instance.get_instance_id()
+                    );
+                    let dialog = ConfirmDialog::new(format!(" {} instance ", verb), message);
+                    self.pending_state_change = Some((dialog, instance, change));
+                }
+                _ => {}
+            }
+            return Ok(Action::Noop);
+        }
         if self.search_active {
             let action = self.search_component.handle_action(action)?;
             match action {
@@ -95,6 +329,19 @@ impl HandleAction for InstanceSelection {
                     self.instance_details.set_instance(item);
                     Ok(Action::Noop)
                 }
+                Action::OpenInstanceActions(item) => {
+                    self.action_menu = Some(ActionMenu::new(item));
+                    Ok(Action::Noop)
+                }
+                Action::OpenPortForward(item) => {
+                    self.port_forward =
+                        Some(PortForwardDialog::new(item, self.known_hosts.clone()));
+                    Ok(Action::Noop)
+                }
+                Action::OpenRunCommand(instances) => {
+                    self.run_command = Some(RunCommandDialog::new(instances));
+                    Ok(Action::Noop)
+                }
                 other => Ok(other),
             }
         }
@@ -103,6 +350,19 @@ impl HandleAction for InstanceSelection {
 
 impl Render for InstanceSelection {
     fn render(&mut self, frame: &mut Frame, area: Rect) {
+        let area = match self.multi_region {
+            true => {
+                // Summary line plus up to 5 failures, and the borders
+                let height = self.region_failures.len().min(5) as u16 + 3;
+                let layout = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints(vec![Constraint::Length(height), Constraint::Fill(1)])
+                    .split(area);
+                self.render_region_progress(frame, layout[0]);
+                layout[1]
+            }
+            false => area,
+        };
         let vertical_layout = Layout::default()
             .direction(Direction::Vertical)
             .constraints(vec![Constraint::Percentage(90), Constraint::Percentage(10)])
@@ -130,5 +390,21 @@ impl Render for InstanceSelection {
             self.instances_table_component
                 .render(frame, vertical_layout[0]);
         }
+
+        if let Some(menu) = &mut self.action_menu {
+            menu.render(frame, area);
+        }
+        if let Some((dialog, _, _)) = &mut self.pending_state_change {
+            dialog.render(frame, area);
+        }
+        if let Some(dialog) = &mut self.port_forward {
+            dialog.render(frame, area);
+        }
+        if let Some(dialog) = &mut self.run_command {
+            dialog.render(frame, area);
+        }
+        if let Some(dialog) = &mut self.session_document {
+            dialog.render(frame, area);
+        }
     }
 }
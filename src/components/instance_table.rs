@@ -1,4 +1,7 @@
+use std::collections::HashSet;
+
 use crate::aws::InstanceInfo;
+use crate::session::Session;
 use crossterm::event::{Event, KeyCode};
 use ratatui::{
     layout::{Constraint, Rect},
@@ -17,6 +20,13 @@ pub struct InstanceTable {
     visible_items: Vec<InstanceInfo>,
     filter: String,
     recent_first: bool,
+    show_region: bool,
+    online_only: bool,
+    all_states: bool,
+    loading_indicator: Option<String>,
+    cache_note: Option<String>,
+    /// Ids of the instances selected with space, kept across filters and refreshes
+    marked: HashSet<String>,
 }
 
 impl InstanceTable {
@@ -29,13 +39,64 @@ impl InstanceTable {
             visible_items: items.clone(),
             filter: String::default(),
             recent_first: false,
+            show_region: false,
+            online_only: false,
+            all_states: false,
+            loading_indicator: None,
+            cache_note: None,
+            marked: HashSet::new(),
         }
     }
 
-    pub fn with_items_and_filter(items: Vec<InstanceInfo>, filter: String) -> InstanceTable {
-        let mut table = InstanceTable::with_items(items);
-        table.apply_filter(filter);
-        table
+    pub fn set_show_region(&mut self, show_region: bool) {
+        self.show_region = show_region;
+    }
+
+    /// Shown in the table title while instances are being fetched
+    pub fn set_loading_indicator(&mut self, indicator: Option<String>) {
+        self.loading_indicator = indicator;
+    }
+
+    /// Shown in the table title while the instances come from the cache
+    pub fn set_cache_note(&mut self, note: Option<String>) {
+        self.cache_note = note;
+    }
+
+    /// Replaces every instance, keeping the current filter and, when it is still there,
+    /// the selected instance
+    pub fn set_items(&mut self, items: Vec<InstanceInfo>) {
+        let selected = self.current().map(|instance| instance.get_instance_id());
+        self.items = items;
+        let ids: HashSet<String> = self.items.iter().map(|i| i.get_instance_id()).collect();
+        self.marked.retain(|id| ids.contains(id));
+        self.apply_filter(self.filter.clone());
+        if let Some(i) = selected.and_then(|id| {
+            self.visible_items
+                .iter()
+                .position(|instance| instance.get_instance_id() == id)
+        }) {
+            self.state.select(Some(i));
+        }
+    }
+
+    /// Adds instances to the table, keeping the current filter
+    pub fn append_items(&mut self, items: Vec<InstanceInfo>) {
+        self.items.extend(items);
+        self.apply_filter(self.filter.clone());
+    }
+
+    /// Every instance, including the ones hidden by the filter
+    pub fn get_items(&self) -> &[InstanceInfo] {
+        &self.items
+    }
+
+    /// Swaps in a freshly fetched version of an instance
+    pub fn replace_item(&mut self, instance: InstanceInfo) {
+        for item in self.items.iter_mut().chain(self.visible_items.iter_mut()) {
+            if item.get_instance_id() == instance.get_instance_id() {
+                *item = instance.clone();
+            }
+        }
     }
 
     pub fn apply_filter(&mut self, filter: String) {
@@ -49,10 +110,15 @@ impl InstanceTable {
                     .to_lowercase()
                     .contains(&self.filter.to_lowercase())
             })
+            // Instances whose status couldn't be fetched may well be online
+            .filter(|instance| {
+                !self.online_only || instance.is_ssm_online() || instance.is_ssm_unknown()
+            })
+            .filter(|instance| self.all_states || instance.is_running())
             .cloned()
             .collect();
         self.sort_instances();
-        self.state.select(if self.visible_items.len() != 0 {Some(0)} else {None});
+        self.state.select(if !self.visible_items.is_empty() {Some(0)} else {None});
 
     }
 
@@ -101,7 +167,43 @@ impl InstanceTable {
     }
 
     pub fn current(&self) -> Option<InstanceInfo> {
-        self.state.selected().map(|i| self.visible_items[i].clone())
+        self.state
+            .selected()
+            .and_then(|i| self.visible_items.get(i).cloned())
+    }
+
+    fn toggle_mark(&mut self) {
+        let Some(instance) = self.current() else {
+            return;
+        };
+        if !self.marked.remove(&instance.get_instance_id()) {
+            self.marked.insert(instance.get_instance_id());
+        }
+        self.next();
+    }
+
+    /// Selects every visible instance, or clears them when they all already are
+    fn toggle_mark_visible(&mut self) {
+        let ids: Vec<String> = self.visible_items.iter().map(|i| i.get_instance_id()).collect();
+        if ids.iter().all(|id| self.marked.contains(id)) {
+            for id in ids {
+                self.marked.remove(&id);
+            }
+        } else {
+            self.marked.extend(ids);
+        }
+    }
+
+    /// The selected instances, or the current one when none is
+    pub fn get_targets(&self) -> Vec<InstanceInfo> {
+        if self.marked.is_empty() {
+            return self.current().into_iter().collect();
+        }
+        self.items
+            .iter()
+            .filter(|instance| self.marked.contains(&instance.get_instance_id()))
+            .cloned()
+            .collect()
     }
 
     fn perform_key_action(&mut self, action: Option<&str>) -> Action {
@@ -110,9 +212,11 @@ impl InstanceTable {
                 Some("nextItem") => self.next(),
                 Some("previousItem") => self.previous(),
                 Some("accessItem") => {
-                    return match self.current() {
-                        Some(item) => Action::ReturnInstance(item),
-                        None => Action::Noop,
+                    let mut targets = self.get_targets();
+                    return match targets.len() {
+                        0 => Action::Noop,
+                        1 => Action::ReturnInstance(targets.remove(0)),
+                        _ => Action::StartSession(Session::Shells(targets)),
                     };
                 }
                 _ => {}
@@ -142,6 +246,46 @@ impl HandleAction for InstanceTable {
                     self.sort_instances();
                     Action::Noop
                 }
+                KeyCode::Char('m') => {
+                    self.online_only = !self.online_only;
+                    self.apply_filter(self.filter.clone());
+                    Action::Noop
+                }
+                KeyCode::Char('s') => {
+                    self.all_states = !self.all_states;
+                    self.apply_filter(self.filter.clone());
+                    Action::Noop
+                }
+                KeyCode::F(5) | KeyCode::Char('R') => Action::Refresh,
+                KeyCode::Esc => Action::Cancel,
+                KeyCode::Char('a') => match self.current() {
+                    Some(item) => Action::OpenInstanceActions(item),
+                    None => Action::Noop,
+                },
+                KeyCode::Char('p') => match self.current() {
+                    Some(item) => Action::OpenPortForward(item),
+                    None => Action::Noop,
+                },
+                KeyCode::Char('o') => match self.current() {
+                    Some(item) => Action::OpenSessionDocument(item),
+                    None => Action::Noop,
+                },
+                KeyCode::Char('d') => match self.current() {
+                    Some(item) => Action::OpenDatabases(item.get_region()),
+                    None => Action::Noop,
+                },
+                KeyCode::Char(' ') => {
+                    self.toggle_mark();
+                    Action::Noop
+                }
+                KeyCode::Char('*') => {
+                    self.toggle_mark_visible();
+                    Action::Noop
+                }
+                KeyCode::Char('c') => match self.get_targets() {
+                    targets if targets.is_empty() => Action::Noop,
+                    targets => Action::OpenRunCommand(targets),
+                },
                 _ => Action::Noop,
             },
             _ => Action::Noop,
@@ -152,19 +296,34 @@ impl HandleAction for InstanceTable {
 
 #[allow(refining_impl_trait)]
 impl View for InstanceTable {
-    fn get_widget(&self) -> Table {
+    fn get_widget(&self) -> Table<'_> {
         let items: Vec<Row> = self
             .visible_items
             .iter()
-            .cloned()
             .map(|i| {
-                Row::new(vec![
-                    Cell::from(i.get_name()),
+                let name = match self.marked.contains(&i.get_instance_id()) {
+                    true => format!("✓ {}", i.get_name()),
+                    false => i.get_name(),
+                };
+                let mut cells = vec![
+                    Cell::from(name),
                     Cell::from(i.get_instance_id()),
                     Cell::from(i.get_private_ip()),
                     Cell::from(i.get_public_ip()),
-                ])
-                .style(if self.recent_first && i.get_last_access().is_some() {
+                    Cell::from(i.get_ssm_status()),
+                ];
+                if self.all_states {
+                    cells.push(Cell::from(i.get_state()));
+                }
+                if self.show_region {
+                    cells.push(Cell::from(i.get_region().to_string()));
+                }
+                Row::new(cells)
+                .style(if !i.is_running() {
+                    Style::default().fg(Color::DarkGray)
+                } else if !i.is_ssm_online() && !i.is_ssm_unknown() {
+                    Style::default().fg(Color::Red)
+                } else if self.recent_first && i.get_last_access().is_some() {
                     Style::default().fg(Color::Yellow)
                 } else {
                     Style::default()
@@ -172,15 +331,31 @@ impl View for InstanceTable {
                 .height(1)
             })
             .collect();
-        let widths = [
-            Constraint::Percentage(25),
-            Constraint::Percentage(25),
-            Constraint::Percentage(25),
-            Constraint::Percentage(25),
-        ];
+        let mut header = vec!["Name", "InstanceId", "Private IP", "Public IP", "SSM Status"];
+        if self.all_states {
+            header.push("State");
+        }
+        if self.show_region {
+            header.push("Region");
+        }
+        let widths = vec![Constraint::Ratio(1, header.len() as u32); header.len()];
         // Create a List from all list items and highlight the currently selected one
+        let mut title = if self.visible_items.len() == self.items.len() {
+            format!(" {} instances ", self.items.len())
+        } else {
+            format!(" {} of {} instances ", self.visible_items.len(), self.items.len())
+        };
+        if !self.marked.is_empty() {
+            title = format!("{}({} selected) ", title, self.marked.len());
+        }
+        if let Some(note) = &self.cache_note {
+            title = format!("{}({}) ", title, note);
+        }
+        if let Some(indicator) = &self.loading_indicator {
+            title = format!(" {}{}", indicator, title);
+        }
         Table::new(items, widths)
-            .block(Block::default().borders(Borders::ALL))
+            .block(Block::default().borders(Borders::ALL).title(title))
             .row_highlight_style(
                 Style::default()
                     .bg(Color::LightGreen)
@@ -188,7 +363,7 @@ impl View for InstanceTable {
             )
             .highlight_symbol(">> ")
             .header(
-                Row::new(vec!["Name", "InstanceId", "Private IP", "Public IP"])
+                Row::new(header)
                     .style(Style::default().add_modifier(Modifier::BOLD).underlined()),
             )
     }
@@ -203,21 +378,79 @@ impl Render for InstanceTable {
 
 impl RenderHelp for InstanceTable {
     fn render_help(&mut self, frame: &mut Frame, area: Rect) {
-        let rows = vec![Row::new(vec![
-            Cell::from(Span::styled(
-                "'/' Search",
-                Style::default().fg(Color::White),
-            )),
-            Cell::from(Span::styled("'q' Exit", Style::default().fg(Color::White))),
-            Cell::from(Span::styled(
-                "'i' Info Panel",
-                Style::default().fg(Color::White),
-            )),
-            Cell::from(Span::styled(
-                "'r' Show Recent First",
-                Style::default().fg(Color::White),
-            )),
-        ])];
+        let rows = vec![
+            Row::new(vec![
+                Cell::from(Span::styled(
+                    "'/' Search",
+                    Style::default().fg(Color::White),
+                )),
+                Cell::from(Span::styled("'q' Exit", Style::default().fg(Color::White))),
+                Cell::from(Span::styled(
+                    "'i' Info Panel",
+                    Style::default().fg(Color::White),
+                )),
+                Cell::from(Span::styled(
+                    "'r' Show Recent First",
+                    Style::default().fg(Color::White),
+                )),
+                Cell::from(Span::styled(
+                    "'p' Port Forward",
+                    Style::default().fg(Color::White),
+                )),
+            ]),
+            Row::new(vec![
+                Cell::from(Span::styled(
+                    if self.online_only {
+                        "'m' Show All Instances"
+                    } else {
+                        "'m' Hide Offline Instances"
+                    },
+                    Style::default().fg(Color::White),
+                )),
+                Cell::from(Span::styled(
+                    if self.all_states {
+                        "'s' Running Only"
+                    } else {
+                        "'s' Show All States"
+                    },
+                    Style::default().fg(Color::White),
+                )),
+                Cell::from(Span::styled(
+                    "'a' Start/Stop/Reboot",
+                    Style::default().fg(Color::White),
+                )),
+                Cell::from(Span::styled(
+                    "'d' Databases",
+                    Style::default().fg(Color::White),
+                )),
+                Cell::from(Span::styled(
+                    if self.loading_indicator.is_some() {
+                        "'Esc' Cancel Loading"
+                    } else {
+                        "'R' Refresh"
+                    },
+                    Style::default().fg(Color::White),
+                )),
+            ]),
+            Row::new(vec![
+                Cell::from(Span::styled(
+                    "'Space' Select",
+                    Style::default().fg(Color::White),
+                )),
+                Cell::from(Span::styled(
+                    "'*' Select All Visible",
+                    Style::default().fg(Color::White),
+                )),
+                Cell::from(Span::styled(
+                    "'c' Run Command",
+                    Style::default().fg(Color::White),
+                )),
+                Cell::from(Span::styled(
+                    "'o' Open With Document",
+                    Style::default().fg(Color::White),
+                )),
+            ]),
+        ];
         let table = Table::new(
             rows,
             vec![
@@ -0,0 +1,171 @@
+use crossterm::event::{Event, KeyCode};
+use ratatui::{
+    layout::{Constraint, Flex, Layout, Rect},
+    style::{Color, Style},
+    text::Line,
+    widgets::{Block, Borders, Clear},
+    Frame,
+};
+
+use super::{text_input::TextInput, Action, HandleAction, Render};
+use crate::aws::InstanceInfo;
+use crate::session::Session;
+use anyhow::Result;
+
+const REMOTE_HOST: usize = 0;
+const REMOTE_PORT: usize = 1;
+const LOCAL_PORT: usize = 2;
+
+/// Popup asking where a port forwarding session should lead
+#[derive(Debug, Clone)]
+pub struct PortForwardDialog {
+    instance: InstanceInfo,
+    /// Remote host, remote port and local port
+    inputs: [TextInput; 3],
+    focused: usize,
+    /// Hosts used before, offered with Up/Down in the remote host field
+    known_hosts: Vec<String>,
+    known_host_index: Option<usize>,
+    error: Option<String>,
+}
+
+impl PortForwardDialog {
+    pub fn new(instance: InstanceInfo, known_hosts: Vec<String>) -> PortForwardDialog {
+        PortForwardDialog {
+            instance,
+            inputs: [
+                TextInput::new("Remote host: ".to_string()),
+                TextInput::new("Remote port: ".to_string()),
+                TextInput::new("Local port:  ".to_string()),
+            ],
+            focused: REMOTE_PORT,
+            known_hosts,
+            known_host_index: None,
+            error: None,
+        }
+    }
+
+    fn pick_known_host(&mut self, key: KeyCode) {
+        if self.known_hosts.is_empty() {
+            return;
+        }
+        let last = self.known_hosts.len() - 1;
+        let index = match (key, self.known_host_index) {
+            (KeyCode::Down, Some(i)) if i < last => i + 1,
+            (KeyCode::Down, Some(_)) => 0,
+            (KeyCode::Up, Some(0)) => last,
+            (KeyCode::Up, Some(i)) => i - 1,
+            _ => 0,
+        };
+        self.known_host_index = Some(index);
+        self.inputs[REMOTE_HOST].set_value(self.known_hosts[index].clone());
+    }
+
+    /// The local port defaults to the remote one, an empty host means the instance itself
+    fn parse_session(&self) -> Result<Session, String> {
+        let remote_port: u16 = self.inputs[REMOTE_PORT]
+            .get_value()
+            .trim()
+            .parse()
+            .map_err(|_| "The remote port must be a number between 1 and 65535".to_string())?;
+        let local_port = match self.inputs[LOCAL_PORT].get_value().trim() {
+            "" => remote_port,
+            value => value
+                .parse()
+                .map_err(|_| "The local port must be a number between 1 and 65535".to_string())?,
+        };
+        if remote_port == 0 || local_port == 0 {
+            return Err("Port 0 can't be forwarded".to_string());
+        }
+        let remote_host = self.inputs[REMOTE_HOST].get_value().trim().to_string();
+        Ok(Session::PortForward {
+            instance: self.instance.clone(),
+            remote_host: (!remote_host.is_empty()).then_some(remote_host),
+            remote_port,
+            local_port,
+        })
+    }
+}
+
+impl HandleAction for PortForwardDialog {
+    fn handle_action(&mut self, action: Event) -> Result<Action> {
+        if let Event::Key(key) = action {
+            match key.code {
+                KeyCode::Tab => {
+                    self.focused = (self.focused + 1) % self.inputs.len();
+                    return Ok(Action::Noop);
+                }
+                KeyCode::BackTab => {
+                    self.focused = (self.focused + self.inputs.len() - 1) % self.inputs.len();
+                    return Ok(Action::Noop);
+                }
+                _ => {}
+            }
+        }
+        let action = match self.inputs[self.focused].handle_action(action)? {
+            Action::Exit => Action::Exit,
+            Action::ReturnWithKey(key) if self.focused == REMOTE_HOST => {
+                self.pick_known_host(key);
+                Action::Noop
+            }
+            Action::ReturnWithKey(KeyCode::Up) => {
+                self.focused -= 1;
+                Action::Noop
+            }
+            Action::ReturnWithKey(_) => {
+                self.focused = (self.focused + 1) % self.inputs.len();
+                Action::Noop
+            }
+            Action::Return(_) => match self.parse_session() {
+                Ok(session) => Action::StartSession(session),
+                Err(error) => {
+                    self.error = Some(error);
+                    Action::Noop
+                }
+            },
+            _ => Action::Noop,
+        };
+        Ok(action)
+    }
+}
+
+impl Render for PortForwardDialog {
+    fn render(&mut self, frame: &mut Frame, area: Rect) {
+        let [area] = Layout::vertical([Constraint::Length(8)])
+            .flex(Flex::Center)
+            .areas(area);
+        let [area] = Layout::horizontal([Constraint::Percentage(60)])
+            .flex(Flex::Center)
+            .areas(area);
+        frame.render_widget(Clear, area);
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .title(format!(" Port forward through {} ", self.instance.get_name()))
+            .border_style(Style::default().fg(Color::Yellow));
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+
+        let rows: [Rect; 6] = Layout::vertical([Constraint::Length(1); 6]).areas(inner);
+        for (input, row) in self.inputs.iter_mut().zip(rows) {
+            input.render(frame, row);
+        }
+        let hint = match self.focused {
+            REMOTE_HOST if !self.known_hosts.is_empty() => {
+                "Empty for the instance itself, 'Up'/'Down' Previous hosts"
+            }
+            REMOTE_HOST => "Empty for the instance itself",
+            _ => "'Tab' Next field    'Enter' Start    'Esc' Cancel",
+        };
+        let footer = match &self.error {
+            Some(error) => Line::styled(error.as_str(), Style::default().fg(Color::Red)),
+            None => Line::from(hint).centered(),
+        };
+        frame.render_widget(footer, rows[4]);
+
+        let focused = rows[self.focused];
+        frame.set_cursor_position((
+            focused.x + self.inputs[self.focused].get_cursor_position() as u16,
+            focused.y,
+        ));
+    }
+}
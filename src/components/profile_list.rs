@@ -0,0 +1,151 @@
+use super::{Action, HandleAction, Render, RenderHelp, View};
+use crate::aws::profiles::AwsProfile;
+use crossterm::event::{Event, KeyCode};
+use ratatui::{
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style, Stylize},
+    text::Span,
+    widgets::{Block, Borders, Cell, Row, Table, TableState},
+    Frame,
+};
+use anyhow::Result;
+#[derive(Default, Debug, Clone)]
+pub struct ProfileList {
+    state: TableState,
+    items: Vec<AwsProfile>,
+}
+
+impl ProfileList {
+    pub fn with_items(items: Vec<AwsProfile>) -> ProfileList {
+        let mut state = TableState::default();
+        state.select(Some(0));
+        ProfileList { state, items }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    fn next(&mut self) {
+        let i = match self.state.selected() {
+            Some(i) => {
+                if i >= self.items.len() - 1 {
+                    0
+                } else {
+                    i + 1
+                }
+            }
+            None => 0,
+        };
+        self.state.select(Some(i));
+    }
+
+    fn previous(&mut self) {
+        let i = match self.state.selected() {
+            Some(i) => {
+                if i == 0 {
+                    self.items.len() - 1
+                } else {
+                    i - 1
+                }
+            }
+            None => 0,
+        };
+        self.state.select(Some(i));
+    }
+
+    pub fn current(&self) -> Option<AwsProfile> {
+        self.state.selected().and_then(|i| self.items.get(i).cloned())
+    }
+}
+
+impl HandleAction for ProfileList {
+    fn handle_action(&mut self, action: Event) -> Result<Action> {
+        let action = match action {
+            Event::Key(key) => match key.code {
+                KeyCode::Char('q') => Action::Exit,
+                KeyCode::Down => {
+                    self.next();
+                    Action::Noop
+                }
+                KeyCode::Up => {
+                    self.previous();
+                    Action::Noop
+                }
+                KeyCode::Right | KeyCode::Enter => match self.current() {
+                    Some(profile) => Action::Return(profile.get_name()),
+                    None => Action::Noop,
+                },
+                _ => Action::Noop,
+            },
+            _ => Action::Noop,
+        };
+        Ok(action)
+    }
+}
+
+#[allow(refining_impl_trait)]
+impl View for ProfileList {
+    fn get_widget(&self) -> Table<'_> {
+        let items: Vec<Row> = self
+            .items
+            .iter()
+            .map(|profile| {
+                Row::new(vec![
+                    Cell::from(profile.get_name()),
+                    Cell::from(profile.get_region().unwrap_or_default()),
+                    Cell::from(
+                        profile
+                            .get_sso_start_url()
+                            .or_else(|| profile.get_role_arn())
+                            .unwrap_or_default(),
+                    ),
+                ])
+            })
+            .collect();
+        let widths = [
+            Constraint::Percentage(30),
+            Constraint::Percentage(20),
+            Constraint::Percentage(50),
+        ];
+        Table::new(items, widths)
+            .block(Block::default().borders(Borders::ALL))
+            .row_highlight_style(
+                Style::default()
+                    .bg(Color::LightGreen)
+                    .add_modifier(Modifier::BOLD),
+            )
+            .highlight_symbol(">> ")
+            .header(
+                Row::new(vec!["Profile", "Default Region", "SSO Start URL / Role ARN"])
+                    .style(Style::default().add_modifier(Modifier::BOLD).underlined()),
+            )
+    }
+}
+
+impl Render for ProfileList {
+    fn render(&mut self, frame: &mut Frame, area: Rect) {
+        let vertical_layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(vec![Constraint::Percentage(90), Constraint::Percentage(10)])
+            .split(area);
+
+        let widget = self.get_widget();
+        frame.render_stateful_widget(widget, vertical_layout[0], &mut self.state.clone());
+        self.render_help(frame, vertical_layout[1]);
+    }
+}
+
+impl RenderHelp for ProfileList {
+    fn render_help(&mut self, frame: &mut Frame, area: Rect) {
+        let rows = vec![Row::new(vec![
+            Cell::from(Span::styled("'q' Exit", Style::default().fg(Color::White))),
+            Cell::from(Span::styled(
+                "'Enter' Use profile",
+                Style::default().fg(Color::White),
+            )),
+        ])];
+        let table = Table::new(rows, vec![Constraint::Min(10), Constraint::Min(10)]);
+        frame.render_widget(table, area);
+    }
+}
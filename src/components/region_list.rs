@@ -8,30 +8,39 @@ use ratatui::{
     Frame,
 };
 use anyhow::Result;
+
+/// Pseudo region, always listed first, that aggregates the instances of every visible region
+pub const ALL_REGIONS: &str = "All regions";
+
 #[derive(Default, Debug, Clone)]
 pub struct RegionList {
     state: ListState,
     items: Vec<String>,
     favorites: Vec<String>,
+    disabled: Vec<String>,
 }
 
 impl RegionList {
     pub fn with_items(items: Vec<String>) -> RegionList {
         let mut state = ListState::default();
         state.select(Some(0));
-        RegionList {
+        let mut list = RegionList {
             state,
-            items,
+            items: Vec::new(),
             favorites: Vec::new(),
-        }
+            disabled: Vec::new(),
+        };
+        list.update_items(items);
+        list
     }
 
     pub fn update_items(&mut self, items: Vec<String>) {
         self.items = items;
-        if let Some(i) = self.state.selected_mut() {
-            if *i >= self.items.len() {
-                *i = self.items.len() - 1;
-            }
+        self.items.push(ALL_REGIONS.to_string());
+        if let Some(i) = self.state.selected_mut()
+            && *i >= self.items.len()
+        {
+            *i = self.items.len() - 1;
         }
         self.sort_list();
     }
@@ -41,8 +50,26 @@ impl RegionList {
         self.sort_list();
     }
 
+    /// Regions that aren't enabled for the account, they are listed last
+    pub fn set_disabled(&mut self, disabled: Vec<String>) {
+        self.disabled = disabled;
+        self.sort_list();
+    }
+
+    pub fn select_item(&mut self, item: &str) {
+        if let Some(i) = self.items.iter().position(|i| i == item) {
+            self.state.select(Some(i));
+        }
+    }
+
     fn sort_list(&mut self) {
         self.items.sort_by(|a, b| {
+            if a == ALL_REGIONS || b == ALL_REGIONS {
+                return (b == ALL_REGIONS).cmp(&(a == ALL_REGIONS));
+            }
+            if self.disabled.contains(a) != self.disabled.contains(b) {
+                return self.disabled.contains(a).cmp(&self.disabled.contains(b));
+            }
             if self.favorites.contains(a) && !self.favorites.contains(b) {
                 return std::cmp::Ordering::Less;
             }
@@ -91,10 +118,17 @@ impl HandleAction for RegionList {
        let action =  match action {
             Event::Key(key) => match key.code {
                 KeyCode::Char('q') => Action::Exit,
-                KeyCode::Char('h') => Action::Hide(self.current().unwrap()),
+                KeyCode::Char('h') => match self.current() {
+                    Some(region) if region != ALL_REGIONS => Action::Hide(region),
+                    _ => Action::Noop,
+                },
                 KeyCode::Char('r') => Action::Reset,
                 KeyCode::Char('c') => Action::OpenConfig,
-                KeyCode::Char('*') => Action::ToggleFavorite(self.current().unwrap()),
+                KeyCode::Char('t') => Action::OpenTunnels,
+                KeyCode::Char('*') => match self.current() {
+                    Some(region) if region != ALL_REGIONS => Action::ToggleFavorite(region),
+                    _ => Action::Noop,
+                },
                 KeyCode::Down => {
                     self.next();
                     Action::Noop
@@ -104,6 +138,7 @@ impl HandleAction for RegionList {
                     Action::Noop
                 }
                 KeyCode::Right | KeyCode::Enter => match self.current() {
+                    Some(str) if str == ALL_REGIONS => Action::OpenAllRegions,
                     Some(str) => Action::Return(str.to_owned()),
                     None => Action::Noop,
                 },
@@ -117,7 +152,7 @@ impl HandleAction for RegionList {
 
 #[allow(refining_impl_trait)]
 impl View for RegionList {
-    fn get_widget(&self) -> List {
+    fn get_widget(&self) -> List<'_> {
         let items: Vec<ListItem> = self
             .items
             .iter()
@@ -127,7 +162,12 @@ impl View for RegionList {
                 } else {
                     ""
                 };
-                ListItem::new(format!("{} {}", prefix, i))
+                if self.disabled.contains(i) {
+                    ListItem::new(format!("{} {} (not enabled)", prefix, i))
+                        .style(Style::default().fg(Color::DarkGray))
+                } else {
+                    ListItem::new(format!("{} {}", prefix, i))
+                }
             })
             .collect();
 
@@ -175,6 +215,10 @@ impl RenderHelp for RegionList {
                     "'c' to open configuration",
                     Style::default().fg(Color::White),
                 )),
+                Cell::from(Span::styled(
+                    "'t' Saved tunnels",
+                    Style::default().fg(Color::White),
+                )),
         ])];
         let table = Table::new(
             rows,
@@ -0,0 +1,71 @@
+use crossterm::event::Event;
+use ratatui::{
+    layout::{Constraint, Flex, Layout, Rect},
+    style::{Color, Style},
+    text::Line,
+    widgets::{Block, Borders, Clear},
+    Frame,
+};
+
+use super::{text_input::TextInput, Action, HandleAction, Render};
+use crate::aws::InstanceInfo;
+use anyhow::Result;
+
+/// Popup asking for the shell command to run on the selected instances
+#[derive(Debug, Clone)]
+pub struct RunCommandDialog {
+    instances: Vec<InstanceInfo>,
+    input: TextInput,
+}
+
+impl RunCommandDialog {
+    pub fn new(instances: Vec<InstanceInfo>) -> RunCommandDialog {
+        RunCommandDialog {
+            instances,
+            input: TextInput::new("$ ".to_string()),
+        }
+    }
+}
+
+impl HandleAction for RunCommandDialog {
+    fn handle_action(&mut self, action: Event) -> Result<Action> {
+        let action = match self.input.handle_action(action)? {
+            Action::Exit => Action::Exit,
+            Action::Return(command) if !command.trim().is_empty() => {
+                Action::RunCommand(self.instances.clone(), command.trim().to_string())
+            }
+            _ => Action::Noop,
+        };
+        Ok(action)
+    }
+}
+
+impl Render for RunCommandDialog {
+    fn render(&mut self, frame: &mut Frame, area: Rect) {
+        let [area] = Layout::vertical([Constraint::Length(5)])
+            .flex(Flex::Center)
+            .areas(area);
+        let [area] = Layout::horizontal([Constraint::Percentage(60)])
+            .flex(Flex::Center)
+            .areas(area);
+        frame.render_widget(Clear, area);
+        let title = match self.instances.as_slice() {
+            [instance] => format!(" Run command on {} ", instance.get_name()),
+            instances => format!(" Run command on {} instances ", instances.len()),
+        };
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .title(title)
+            .border_style(Style::default().fg(Color::Yellow));
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+
+        let [input, _, hint] = Layout::vertical([Constraint::Length(1); 3]).areas(inner);
+        self.input.render(frame, input);
+        frame.render_widget(
+            Line::from("Runs with sh on Linux and PowerShell on Windows, 'Esc' Cancel").centered(),
+            hint,
+        );
+        frame.set_cursor_position((input.x + self.input.get_cursor_position() as u16, input.y));
+    }
+}
@@ -0,0 +1,176 @@
+use crossterm::event::{Event, KeyCode, KeyModifiers};
+use ratatui::{
+    layout::{Constraint, Flex, Layout, Rect},
+    style::{Color, Style},
+    text::Line,
+    widgets::{Block, Borders, Clear},
+    Frame,
+};
+
+use super::{text_input::TextInput, Action, HandleAction, Render};
+use crate::app::config::{DocumentRule, SessionDocument};
+use crate::aws::InstanceInfo;
+use crate::session::Session;
+use anyhow::Result;
+
+const DOCUMENT: usize = 0;
+const PARAMETERS: usize = 1;
+const TARGET: usize = 2;
+
+/// Popup asking for the SSM document, and its parameters, a shell should start
+#[derive(Debug, Clone)]
+pub struct SessionDocumentDialog {
+    instance: InstanceInfo,
+    /// Document name, parameters and the instance id or tag `Ctrl+S` saves them for
+    inputs: [TextInput; 3],
+    focused: usize,
+    error: Option<String>,
+}
+
+impl SessionDocumentDialog {
+    /// The fields are filled with the rule giving the default document of the instance,
+    /// if any
+    pub fn new(instance: InstanceInfo, default: Option<DocumentRule>) -> SessionDocumentDialog {
+        let mut inputs = [
+            TextInput::new("Document:   ".to_string()),
+            TextInput::new("Parameters: ".to_string()),
+            TextInput::new("Save for:   ".to_string()),
+        ];
+        match default {
+            Some(rule) => {
+                inputs[PARAMETERS].set_value(rule.document.format_parameters());
+                inputs[DOCUMENT].set_value(rule.document.name);
+                inputs[TARGET].set_value(rule.target);
+            }
+            None => {
+                inputs[DOCUMENT].set_value("AWS-StartInteractiveCommand".to_string());
+                inputs[TARGET].set_value(instance.get_instance_id());
+            }
+        }
+        SessionDocumentDialog {
+            instance,
+            inputs,
+            focused: PARAMETERS,
+            error: None,
+        }
+    }
+
+    /// `None` when the document name is left empty, for the default shell
+    fn parse_document(&self) -> Result<Option<SessionDocument>, String> {
+        let name = self.inputs[DOCUMENT].get_value().trim().to_string();
+        if name.is_empty() {
+            return Ok(None);
+        }
+        let parameters = SessionDocument::parse_parameters(&self.inputs[PARAMETERS].get_value())
+            .map_err(|e| format!("{:#}", e))?;
+        Ok(Some(SessionDocument { name, parameters }))
+    }
+
+    fn focus_next(&mut self) {
+        self.focused = (self.focused + 1) % self.inputs.len();
+    }
+
+    fn focus_previous(&mut self) {
+        self.focused = (self.focused + self.inputs.len() - 1) % self.inputs.len();
+    }
+
+    fn save(&mut self) -> Action {
+        let saved = DocumentRule::parse_target(&self.inputs[TARGET].get_value())
+            .map_err(|e| format!("{:#}", e))
+            .and_then(|target| Ok((target, self.parse_document()?)));
+        match saved {
+            Ok((target, document)) => Action::SaveSessionDocument(target, document),
+            Err(error) => {
+                self.error = Some(error);
+                Action::Noop
+            }
+        }
+    }
+}
+
+impl HandleAction for SessionDocumentDialog {
+    fn handle_action(&mut self, action: Event) -> Result<Action> {
+        if let Event::Key(key) = action {
+            match key.code {
+                KeyCode::Tab => {
+                    self.focus_next();
+                    return Ok(Action::Noop);
+                }
+                KeyCode::BackTab => {
+                    self.focus_previous();
+                    return Ok(Action::Noop);
+                }
+                KeyCode::Char('s') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    return Ok(self.save());
+                }
+                _ => {}
+            }
+        }
+        let action = match self.inputs[self.focused].handle_action(action)? {
+            Action::Exit => Action::Exit,
+            Action::ReturnWithKey(KeyCode::Up) => {
+                self.focus_previous();
+                Action::Noop
+            }
+            Action::ReturnWithKey(KeyCode::Down) => {
+                self.focus_next();
+                Action::Noop
+            }
+            Action::Return(_) => match self.parse_document() {
+                Ok(Some(document)) => Action::StartSession(Session::Document {
+                    instance: self.instance.clone(),
+                    document,
+                }),
+                Ok(None) => Action::StartSession(Session::Shell(self.instance.clone())),
+                Err(error) => {
+                    self.error = Some(error);
+                    Action::Noop
+                }
+            },
+            _ => Action::Noop,
+        };
+        Ok(action)
+    }
+}
+
+impl Render for SessionDocumentDialog {
+    fn render(&mut self, frame: &mut Frame, area: Rect) {
+        let [area] = Layout::vertical([Constraint::Length(8)])
+            .flex(Flex::Center)
+            .areas(area);
+        let [area] = Layout::horizontal([Constraint::Percentage(60)])
+            .flex(Flex::Center)
+            .areas(area);
+        frame.render_widget(Clear, area);
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .title(format!(" Open {} with ", self.instance.get_name()))
+            .border_style(Style::default().fg(Color::Yellow));
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+
+        let rows: [Rect; 6] = Layout::vertical([Constraint::Length(1); 6]).areas(inner);
+        for (input, row) in self.inputs.iter_mut().zip(rows) {
+            input.render(frame, row);
+        }
+        let hint = match self.focused {
+            PARAMETERS => "key=value or a JSON object, e.g. command=sudo -iu app",
+            TARGET => "Instance id, or a tag as Key=Value, e.g. Role=web",
+            _ => "Empty for the default shell",
+        };
+        frame.render_widget(Line::from(hint).centered(), rows[3]);
+        let footer = match &self.error {
+            Some(error) => Line::styled(error.as_str(), Style::default().fg(Color::Red)),
+            None => {
+                Line::from("'Enter' Start    'Ctrl+S' Save as default    'Esc' Cancel").centered()
+            }
+        };
+        frame.render_widget(footer, rows[5]);
+
+        let focused = rows[self.focused];
+        frame.set_cursor_position((
+            focused.x + self.inputs[self.focused].get_cursor_position() as u16,
+            focused.y,
+        ));
+    }
+}
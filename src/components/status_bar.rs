@@ -0,0 +1,82 @@
+use std::time::{Duration, Instant};
+
+use ratatui::{
+    layout::Rect,
+    style::{Color, Style},
+    text::{Line, Span},
+    Frame,
+};
+
+use super::Render;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Level {
+    Info,
+    Warning,
+    Error,
+}
+
+impl Level {
+    /// Errors stay up longer, they are more likely to need reading twice
+    fn get_duration(&self) -> Duration {
+        match self {
+            Level::Info => Duration::from_secs(5),
+            Level::Warning => Duration::from_secs(10),
+            Level::Error => Duration::from_secs(15),
+        }
+    }
+
+    fn get_color(&self) -> Color {
+        match self {
+            Level::Info => Color::Green,
+            Level::Warning => Color::Yellow,
+            Level::Error => Color::Red,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Notification {
+    level: Level,
+    message: String,
+    expires_at: Instant,
+}
+
+/// Bottom line of the app showing the latest message, until it expires
+#[derive(Debug, Default)]
+pub struct StatusBar {
+    notifications: Vec<Notification>,
+}
+
+impl StatusBar {
+    pub fn push(&mut self, level: Level, message: String) {
+        self.notifications.push(Notification {
+            level,
+            // The bar is a single line
+            message: message.replace('\n', " "),
+            expires_at: Instant::now() + level.get_duration(),
+        });
+    }
+}
+
+impl Render for StatusBar {
+    fn render(&mut self, frame: &mut Frame, area: Rect) {
+        let now = Instant::now();
+        self.notifications
+            .retain(|notification| notification.expires_at > now);
+        let Some(notification) = self.notifications.last() else {
+            return;
+        };
+        let mut spans = vec![Span::styled(
+            notification.message.as_str(),
+            Style::default().fg(notification.level.get_color()),
+        )];
+        if self.notifications.len() > 1 {
+            spans.push(Span::styled(
+                format!("  (+{} more)", self.notifications.len() - 1),
+                Style::default().fg(Color::DarkGray),
+            ));
+        }
+        frame.render_widget(Line::from(spans), area);
+    }
+}
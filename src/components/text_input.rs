@@ -145,7 +145,7 @@ impl HandleAction for TextInput {
 
 #[allow(refining_impl_trait)]
 impl View for TextInput {
-    fn get_widget(&self) -> Paragraph {
+    fn get_widget(&self) -> Paragraph<'_> {
         let text: String = format!("{}{}", self.prompt, self.search_input);
         Paragraph::new(Text::from(text))
     }
@@ -0,0 +1,175 @@
+use super::{Action, HandleAction, Render, RenderHelp, View};
+use crate::app::config::Tunnel;
+use crossterm::event::{Event, KeyCode};
+use ratatui::{
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style, Stylize},
+    text::Span,
+    widgets::{Block, Borders, Cell, Row, Table, TableState},
+    Frame,
+};
+use anyhow::Result;
+
+/// Saved port forwarding sessions, the first nine can be started with their number key
+#[derive(Default, Debug, Clone)]
+pub struct TunnelList {
+    state: TableState,
+    items: Vec<Tunnel>,
+}
+
+impl TunnelList {
+    pub fn update_items(&mut self, items: Vec<Tunnel>) {
+        self.items = items;
+        let selected = match self.state.selected() {
+            Some(i) => i.min(self.items.len().saturating_sub(1)),
+            None => 0,
+        };
+        self.state.select(Some(selected));
+    }
+
+    fn next(&mut self) {
+        if self.items.is_empty() {
+            return;
+        }
+        let i = match self.state.selected() {
+            Some(i) => {
+                if i >= self.items.len() - 1 {
+                    0
+                } else {
+                    i + 1
+                }
+            }
+            None => 0,
+        };
+        self.state.select(Some(i));
+    }
+
+    fn previous(&mut self) {
+        if self.items.is_empty() {
+            return;
+        }
+        let i = match self.state.selected() {
+            Some(i) => {
+                if i == 0 {
+                    self.items.len() - 1
+                } else {
+                    i - 1
+                }
+            }
+            None => 0,
+        };
+        self.state.select(Some(i));
+    }
+
+    fn current(&self) -> Option<Tunnel> {
+        self.state.selected().and_then(|i| self.items.get(i).cloned())
+    }
+}
+
+impl HandleAction for TunnelList {
+    fn handle_action(&mut self, action: Event) -> Result<Action> {
+        let action = match action {
+            Event::Key(key) => match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => Action::Exit,
+                KeyCode::Down => {
+                    self.next();
+                    Action::Noop
+                }
+                KeyCode::Up => {
+                    self.previous();
+                    Action::Noop
+                }
+                KeyCode::Right | KeyCode::Enter => match self.current() {
+                    Some(tunnel) => Action::LaunchTunnel(tunnel),
+                    None => Action::Noop,
+                },
+                KeyCode::Char(c @ '1'..='9') => {
+                    let index = c as usize - '1' as usize;
+                    match self.items.get(index) {
+                        Some(tunnel) => Action::LaunchTunnel(tunnel.clone()),
+                        None => Action::Noop,
+                    }
+                }
+                KeyCode::Char('d') => match self.current() {
+                    Some(tunnel) => Action::DeleteTunnel(tunnel),
+                    None => Action::Noop,
+                },
+                _ => Action::Noop,
+            },
+            _ => Action::Noop,
+        };
+        Ok(action)
+    }
+}
+
+#[allow(refining_impl_trait)]
+impl View for TunnelList {
+    fn get_widget(&self) -> Table<'_> {
+        let items: Vec<Row> = self
+            .items
+            .iter()
+            .enumerate()
+            .map(|(i, tunnel)| {
+                Row::new(vec![
+                    Cell::from(if i < 9 { (i + 1).to_string() } else { String::new() }),
+                    Cell::from(format!("localhost:{}", tunnel.local_port)),
+                    Cell::from(tunnel.get_destination()),
+                    Cell::from(format!("{} ({})", tunnel.instance_name, tunnel.instance_id)),
+                    Cell::from(tunnel.region.clone()),
+                    Cell::from(tunnel.profile.clone().unwrap_or_default()),
+                ])
+            })
+            .collect();
+        let widths = [
+            Constraint::Length(3),
+            Constraint::Ratio(1, 6),
+            Constraint::Ratio(1, 4),
+            Constraint::Ratio(1, 4),
+            Constraint::Ratio(1, 8),
+            Constraint::Ratio(1, 8),
+        ];
+        Table::new(items, widths)
+            .block(Block::default().borders(Borders::ALL).title(" Tunnels "))
+            .row_highlight_style(
+                Style::default()
+                    .bg(Color::LightGreen)
+                    .add_modifier(Modifier::BOLD),
+            )
+            .highlight_symbol(">> ")
+            .header(
+                Row::new(vec!["", "Local", "Destination", "Through", "Region", "Profile"])
+                    .style(Style::default().add_modifier(Modifier::BOLD).underlined()),
+            )
+    }
+}
+
+impl Render for TunnelList {
+    fn render(&mut self, frame: &mut Frame, area: Rect) {
+        let vertical_layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(vec![Constraint::Percentage(90), Constraint::Percentage(10)])
+            .split(area);
+
+        let widget = self.get_widget();
+        frame.render_stateful_widget(widget, vertical_layout[0], &mut self.state.clone());
+        self.render_help(frame, vertical_layout[1]);
+    }
+}
+
+impl RenderHelp for TunnelList {
+    fn render_help(&mut self, frame: &mut Frame, area: Rect) {
+        let rows = vec![Row::new(vec![
+            Cell::from(Span::styled("'q' Back", Style::default().fg(Color::White))),
+            Cell::from(Span::styled(
+                "'Enter' / '1'-'9' Start tunnel",
+                Style::default().fg(Color::White),
+            )),
+            Cell::from(Span::styled("'d' Delete", Style::default().fg(Color::White))),
+        ])];
+        let table = Table::new(
+            rows,
+            vec![Constraint::Min(10), Constraint::Min(10), Constraint::Min(10)],
+        );
+        frame.render_widget(table, area);
+    }
+}
@@ -1,58 +1,154 @@
 mod aws;
-use aws::InstanceInfo;
+use aws::{profiles::get_effective_profile, InstanceInfo, InventoryProvider};
 mod ui;
-use ui::{restore_terminal, setup_terminal};
+use ui::{restore_terminal, resume_terminal, setup_terminal, suspend_terminal};
 mod app;
-use app::App;
-use std::process::Command;
+use app::{config::Config, App};
+use std::sync::Arc;
 mod components;
 mod history;
-use history::{History, HistoryEntry};
+mod cli;
+use cli::{Cli, Commands};
+mod output;
+mod cache;
+mod session;
+mod ssh_config;
+mod ssh_key;
+use session::{start_session, start_ssh, Session};
+use ssh_key::EphemeralKey;
 
 use anyhow::{Context, Result};
-use signal_hook::{consts::signal::*, iterator::Signals};
+use clap::Parser;
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let provider = cli.get_provider()?;
+    match &cli.command {
+        Some(Commands::Connect(args)) => {
+            let document = args.get_document()?;
+            let instance = args
+                .target
+                .resolve(provider.as_ref(), cli.require_region()?, cli.profile.clone())
+                .await?;
+            let session = match document {
+                Some(document) => Session::Document { instance, document },
+                None => Session::Shell(instance),
+            };
+            start_session(session, &Config::load()?, &cli.get_endpoint_url()?).map(|_| ())
+        }
+        Some(Commands::List(args)) => {
+            let instances: Vec<InstanceInfo> = provider
+                .fetch_instances(cli.require_region()?, cli.profile.clone())
+                .await?
+                .into_iter()
+                .filter(|instance| args.all_states || instance.is_running())
+                .collect();
+            output::write_instances(&mut std::io::stdout().lock(), &instances, args.format)
+        }
+        Some(Commands::Proxy(args)) => {
+            let instance = args
+                .get_target()
+                .resolve(provider.as_ref(), cli.require_region()?, cli.profile.clone())
+                .await?;
+            let session = Session::Ssh {
+                instance,
+                port: args.port,
+            };
+            start_session(session, &Config::load()?, &cli.get_endpoint_url()?).map(|_| ())
+        }
+        Some(Commands::SshConfig(args)) => {
+            let region = cli.require_region()?;
+            let instances = provider
+                .fetch_instances(region.clone(), cli.profile.clone())
+                .await?;
+            let hosts = ssh_config::render_hosts(
+                &instances,
+                &cli.get_proxy_command(&region)?,
+                &args.prefix,
+                &args.user,
+            );
+            if args.print {
+                print!("{}", hosts);
+                return Ok(());
+            }
+            let path = match &args.path {
+                Some(path) => path.clone(),
+                None => ssh_config::get_ssh_config_path()?,
+            };
+            let marker = format!(
+                "sm_connect {} {}",
+                get_effective_profile(&cli.profile),
+                region
+            );
+            ssh_config::write_block(&path, &marker, &hosts)?;
+            let count = hosts.lines().filter(|line| line.starts_with("Host ")).count();
+            println!("Wrote {} hosts to {}", count, path.display());
+            Ok(())
+        }
+        Some(Commands::Ssh(args)) => {
+            let region = cli.require_region()?;
+            let instance = args
+                .target
+                .resolve(provider.as_ref(), region.clone(), cli.profile.clone())
+                .await?;
+            let key = EphemeralKey::generate()?;
+            provider
+                .send_ssh_public_key(&instance, &args.user, &key.get_public_key()?)
+                .await?;
+            start_ssh(
+                &instance,
+                &args.user,
+                args.port,
+                &key,
+                &cli.get_proxy_command(&region)?,
+                &args.ssh_args,
+            )
+        }
+        None => run_tui(&cli, provider).await,
+    }
+}
+
+async fn run_tui(cli: &Cli, provider: Arc<dyn InventoryProvider>) -> Result<()> {
     let mut terminal = setup_terminal().context("setup failed")?;
-    let Ok(mut app) = App::new() else {
+    // Fixture instances must not end up in the cache of real regions
+    let no_cache = cli.no_cache || !provider.is_live();
+    let Ok(mut app) = App::new(provider, cli.profile.clone(), no_cache) else {
         restore_terminal(&mut terminal).context("restore terminal failed")?;
         return Ok(());
     };
-    let selected = app.run(&mut terminal).await;
+    if let Some(region) = cli.get_region() {
+        app.open_region(region);
+    }
+    loop {
+        let selected = app.run(&mut terminal).await;
+        // In stay open mode the app keeps its region, filter and selection for the next session
+        if let Ok(session) = &selected
+            && (cli.stay_open || app.get_stay_open())
+        {
+            let label = session.get_label();
+            suspend_terminal(&mut terminal).context("restore terminal failed")?;
+            let config = app.get_config();
+            let result = cli
+                .get_endpoint_url()
+                .and_then(|endpoint_url| start_session(session.clone(), &config, &endpoint_url));
+            resume_terminal(&mut terminal).context("setup failed")?;
+            app.session_ended(label, result);
+            continue;
+        }
 
-    restore_terminal(&mut terminal).context("restore terminal failed")?;
-    match selected {
-        Err(e) => match e.downcast_ref() {
-            Some(app::RuntimeError::UserExit) => {}
-            _ => {
-                println!("{:?}", e);
+        restore_terminal(&mut terminal).context("restore terminal failed")?;
+        match selected {
+            Err(e) => match e.downcast_ref() {
+                Some(app::RuntimeError::UserExit) => {}
+                _ => {
+                    println!("Error: {:#}", e);
+                }
+            },
+            Ok(session) => {
+                start_session(session, &app.get_config(), &cli.get_endpoint_url()?)?;
             }
-        },
-        Ok(instance) => connect(instance)?,
+        }
+        return Ok(());
     }
-    Ok(())
-}
-
-fn connect(instance: InstanceInfo) -> Result<()> {
-    // Run the AWS command
-    let entry = HistoryEntry::new(instance.get_instance_id());
-    History::save(entry)?;
-    let mut child = Command::new("aws")
-        .args([
-            "--region",
-            instance.get_region().as_ref(),
-            "ssm",
-            "start-session",
-            "--target",
-            &instance.get_instance_id(),
-        ])
-        .spawn()?;
-
-    // Catch SIGINT, SIGSTP signal and do nothing
-    // So that actually ctrl+c / ctrl+z works on the aws ssm session instead of killing / stopping us
-    let mut _signals = Signals::new([SIGINT,SIGTSTP])?;
-
-    child.wait()?;
-    Ok(())
 }
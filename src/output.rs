@@ -0,0 +1,201 @@
+use std::io::Write;
+
+use anyhow::Result;
+use clap::ValueEnum;
+
+use crate::aws::{InstanceInfo, InstanceRecord};
+
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+pub enum Format {
+    #[default]
+    Table,
+    Json,
+    Csv,
+    Tsv,
+}
+
+const COLUMNS: [&str; 12] = [
+    "name",
+    "instance_id",
+    "region",
+    "private_ip",
+    "public_ip",
+    "instance_type",
+    "state",
+    "vpc_id",
+    "launch_time",
+    "last_access",
+    "ssm_status",
+    "tags",
+];
+
+/// Writes the instances to `out` in the requested format
+pub fn write_instances(
+    out: &mut impl Write,
+    instances: &[InstanceInfo],
+    format: Format,
+) -> Result<()> {
+    let records: Vec<InstanceRecord> = instances.iter().map(|i| i.to_record()).collect();
+    match format {
+        Format::Json => {
+            serde_json::to_writer_pretty(&mut *out, &records)?;
+            writeln!(out)?;
+        }
+        Format::Csv => write_delimited(out, &records, ",", escape_csv)?,
+        Format::Tsv => write_delimited(out, &records, "\t", escape_tsv)?,
+        Format::Table => write_table(out, &records)?,
+    }
+    Ok(())
+}
+
+fn record_fields(record: &InstanceRecord) -> [String; 12] {
+    [
+        record.name.clone(),
+        record.instance_id.clone(),
+        record.region.clone(),
+        record.private_ip.clone(),
+        record.public_ip.clone(),
+        record.instance_type.clone(),
+        record.state.clone(),
+        record.vpc_id.clone(),
+        record.launch_time.clone(),
+        record
+            .last_access
+            .map_or(String::default(), |when| when.to_string()),
+        match (&record.managed_node, &record.ssm_error) {
+            (Some(node), _) => node.ping_status.clone(),
+            (None, Some(_)) => "Unknown".to_string(),
+            (None, None) => "Not managed".to_string(),
+        },
+        record
+            .tags
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect::<Vec<String>>()
+            .join(";"),
+    ]
+}
+
+fn write_delimited(
+    out: &mut impl Write,
+    records: &[InstanceRecord],
+    separator: &str,
+    escape: fn(&str) -> String,
+) -> Result<()> {
+    writeln!(out, "{}", COLUMNS.join(separator))?;
+    for record in records {
+        let fields: Vec<String> = record_fields(record).iter().map(|f| escape(f)).collect();
+        writeln!(out, "{}", fields.join(separator))?;
+    }
+    Ok(())
+}
+
+fn escape_csv(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn escape_tsv(field: &str) -> String {
+    field.replace(['\t', '\n', '\r'], " ")
+}
+
+fn write_table(out: &mut impl Write, records: &[InstanceRecord]) -> Result<()> {
+    // Tags don't fit in a table, use one of the other formats to get them
+    let columns = &COLUMNS[..COLUMNS.len() - 1];
+    let rows: Vec<[String; 12]> = records.iter().map(record_fields).collect();
+    let widths: Vec<usize> = columns
+        .iter()
+        .enumerate()
+        .map(|(i, column)| {
+            rows.iter()
+                .map(|row| row[i].chars().count())
+                .chain([column.len()])
+                .max()
+                .unwrap_or_default()
+        })
+        .collect();
+
+    let header: Vec<String> = columns
+        .iter()
+        .zip(&widths)
+        .map(|(column, width)| format!("{:width$}", column.to_uppercase(), width = width))
+        .collect();
+    writeln!(out, "{}", header.join("  ").trim_end())?;
+    for row in rows {
+        let line: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .map(|(field, width)| format!("{:width$}", field, width = width))
+            .collect();
+        writeln!(out, "{}", line.join("  ").trim_end())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aws::fixture::FixtureInventory;
+
+    fn write(records: Vec<InstanceRecord>, format: Format) -> String {
+        let instances: Vec<InstanceInfo> = records
+            .into_iter()
+            .map(|record| InstanceInfo::from_record(record, None))
+            .collect();
+        let mut out = Vec::new();
+        write_instances(&mut out, &instances, format).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn json_reads_back() {
+        let records = FixtureInventory::test_records();
+        let written: Vec<InstanceRecord> =
+            serde_json::from_str(&write(records.clone(), Format::Json)).unwrap();
+        assert_eq!(
+            serde_json::to_value(written).unwrap(),
+            serde_json::to_value(records).unwrap()
+        );
+    }
+
+    #[test]
+    fn csv_escapes_fields() {
+        let mut records = FixtureInventory::test_records();
+        records.truncate(1);
+        records[0].tags.insert("note".to_string(), "a \"b\", c".to_string());
+        let csv = write(records, Format::Csv);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], COLUMNS.join(","));
+        assert_eq!(
+            lines[1],
+            "web-1,i-0a1b2c3d4e5f60001,eu-west-1,10.0.1.10,54.72.10.1,t3.small,running,\
+             vpc-0123456789abcdef0,2025-03-14T09:26:53Z,,Online,\
+             \"Name=web-1;env=prod;note=a \"\"b\"\", c\""
+        );
+    }
+
+    #[test]
+    fn tsv_has_one_line_per_instance() {
+        let mut records = FixtureInventory::test_records();
+        records[0].tags.insert("note".to_string(), "a\tb\nc".to_string());
+        let tsv = write(records, Format::Tsv);
+        let lines: Vec<&str> = tsv.lines().collect();
+        assert_eq!(lines.len(), 6);
+        assert!(lines.iter().all(|line| line.split('\t').count() == COLUMNS.len()));
+        assert!(lines[1].ends_with("Name=web-1;env=prod;note=a b c"));
+        assert!(lines[5].contains("\tNot managed\t"));
+    }
+
+    #[test]
+    fn table_aligns_columns_without_tags() {
+        let table = write(FixtureInventory::test_records(), Format::Table);
+        let lines: Vec<&str> = table.lines().collect();
+        assert!(lines[0].starts_with("NAME     INSTANCE_ID          REGION"));
+        assert!(lines[0].ends_with("SSM_STATUS"));
+        assert!(lines[1].starts_with("web-1    i-0a1b2c3d4e5f60001  eu-west-1"));
+        assert!(!table.contains("env=prod"));
+    }
+}
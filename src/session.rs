@@ -0,0 +1,477 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::net::{Ipv4Addr, TcpListener};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+use std::process::{Command, ExitStatus};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use anyhow::Result;
+use signal_hook::{consts::signal::*, iterator::Signals};
+use thiserror::Error;
+
+use crate::app::config::{Config, SessionDocument, TmuxLayout};
+use crate::aws::{profiles::get_effective_profile, InstanceInfo};
+use crate::history::{History, HistoryEntry};
+use crate::ssh_key::EphemeralKey;
+
+/// Session Manager session to start once the TUI is closed
+#[derive(Debug, Clone)]
+pub enum Session {
+    Shell(InstanceInfo),
+    /// Shell started with another SSM document than the default of the instance
+    Document {
+        instance: InstanceInfo,
+        document: SessionDocument,
+    },
+    /// Shells on several instances, opened side by side in tmux
+    Shells(Vec<InstanceInfo>),
+    /// Forwards a local port to a port of the instance or, when `remote_host` is set,
+    /// of a host reachable from it
+    PortForward {
+        instance: InstanceInfo,
+        remote_host: Option<String>,
+        remote_port: u16,
+        local_port: u16,
+    },
+    /// Carries an ssh connection to the port of the instance over stdin and stdout,
+    /// as an ssh ProxyCommand
+    Ssh { instance: InstanceInfo, port: u16 },
+}
+
+impl Session {
+    /// What the session connects to, as told in the status bar
+    pub fn get_label(&self) -> String {
+        match self {
+            Session::Shell(instance)
+            | Session::Document { instance, .. }
+            | Session::Ssh { instance, .. } => instance.get_name(),
+            Session::Shells(instances) => format!("{} instances", instances.len()),
+            Session::PortForward {
+                instance,
+                remote_host,
+                remote_port,
+                local_port,
+            } => format!(
+                "localhost:{} to {}:{}",
+                local_port,
+                remote_host.as_deref().unwrap_or(&instance.get_name()),
+                remote_port
+            ),
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum SessionError {
+    #[error("Opening several sessions at once needs tmux and a tmux layout set in the config")]
+    TmuxRequired,
+    #[error("tmux failed: {0}")]
+    Tmux(String),
+    #[error("invalid session command: {0}")]
+    CommandTemplate(String),
+}
+
+/// `preferred` when nothing listens on it locally, else the next free port after it
+pub fn free_local_port(preferred: u16) -> u16 {
+    (preferred..=u16::MAX)
+        .take(100)
+        .find(|port| TcpListener::bind((Ipv4Addr::LOCALHOST, *port)).is_ok())
+        .or_else(|| {
+            let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).ok()?;
+            Some(listener.local_addr().ok()?.port())
+        })
+        .unwrap_or(preferred)
+}
+
+/// Whether shells open in tmux windows or panes rather than in the current terminal
+pub fn is_tmux_active(layout: TmuxLayout) -> bool {
+    layout != TmuxLayout::Off && std::env::var_os("TMUX").is_some_and(|tmux| !tmux.is_empty())
+}
+
+/// Splits a session command template into arguments, as a shell would for plain words
+/// and single or double quoted ones
+pub fn split_command_template(template: &str) -> Result<Vec<String>> {
+    let mut args = Vec::new();
+    let mut current: Option<String> = None;
+    let mut quote: Option<char> = None;
+    for c in template.chars() {
+        match (quote, c) {
+            (Some(open), c) if c == open => quote = None,
+            (Some(_), c) => current.get_or_insert_default().push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                current.get_or_insert_default();
+            }
+            (None, c) if c.is_whitespace() => args.extend(current.take()),
+            (None, c) => current.get_or_insert_default().push(c),
+        }
+    }
+    if quote.is_some() {
+        return Err(SessionError::CommandTemplate("unclosed quote".to_string()).into());
+    }
+    args.extend(current);
+    if args.is_empty() {
+        return Err(SessionError::CommandTemplate("empty command".to_string()).into());
+    }
+    Ok(args)
+}
+
+/// The session command template of the config, with the placeholders of the instance
+/// replaced, e.g. `aws-vault exec {profile} -- aws ssm start-session --target {instance_id}`
+fn templated_command(template: &str, instance: &InstanceInfo) -> Result<Command> {
+    let profile = get_effective_profile(&instance.get_profile());
+    let args: Vec<String> = split_command_template(template)?
+        .into_iter()
+        .map(|arg| {
+            arg.replace("{instance_id}", &instance.get_instance_id())
+                .replace("{region}", instance.get_region().as_ref())
+                .replace("{profile}", &profile)
+                .replace("{name}", &instance.get_name())
+                .replace("{private_ip}", &instance.get_private_ip())
+        })
+        .collect();
+    let mut command = Command::new(&args[0]);
+    command.args(&args[1..]);
+    Ok(command)
+}
+
+/// `aws ssm start-session` to the instance, without the document of the session,
+/// or the session command of the config when set
+fn ssm_command(
+    instance: &InstanceInfo,
+    session_command: &Option<String>,
+    endpoint_url: &Option<String>,
+) -> Result<Command> {
+    if let Some(template) = session_command {
+        let mut command = templated_command(template, instance)?;
+        if let Some(endpoint_url) = endpoint_url {
+            command.args(["--endpoint-url", endpoint_url]);
+        }
+        return Ok(command);
+    }
+    let mut command = Command::new("aws");
+    if let Some(profile) = instance.get_profile() {
+        command.args(["--profile", &profile]);
+    }
+    if let Some(endpoint_url) = endpoint_url {
+        command.args(["--endpoint-url", endpoint_url]);
+    }
+    command.args([
+        "--region",
+        instance.get_region().as_ref(),
+        "ssm",
+        "start-session",
+        "--target",
+        &instance.get_instance_id(),
+    ]);
+    Ok(command)
+}
+
+fn add_document_args(command: &mut Command, document: &SessionDocument) {
+    command.args(["--document-name", &document.name]);
+    if !document.parameters.is_empty() {
+        command.args([
+            "--parameters",
+            &serde_json::json!(document.parameters).to_string(),
+        ]);
+    }
+}
+
+/// Runs `aws ssm start-session` for the session, until the user ends it, and returns its
+/// exit status. Inside tmux, shells open in new windows or panes instead, as set by the
+/// tmux layout of `config`, and `None` is returned right away.
+pub fn start_session(
+    session: Session,
+    config: &Config,
+    endpoint_url: &Option<String>,
+) -> Result<Option<ExitStatus>> {
+    let shells = match &session {
+        Session::Shell(instance) => vec![(instance.clone(), config.get_session_document(instance))],
+        Session::Document { instance, document } => {
+            vec![(instance.clone(), Some(document.clone()))]
+        }
+        Session::Shells(instances) => instances
+            .iter()
+            .map(|instance| (instance.clone(), config.get_session_document(instance)))
+            .collect(),
+        _ => Vec::new(),
+    };
+    let session_command = config.get_session_command();
+    let tmux_layout = config.get_tmux_layout();
+    if !shells.is_empty() && is_tmux_active(tmux_layout) {
+        open_in_tmux(&shells, &session_command, endpoint_url, tmux_layout)?;
+        return Ok(None);
+    }
+    let instance = match &session {
+        Session::Shell(instance) => instance,
+        Session::Document { instance, .. } => instance,
+        Session::PortForward { instance, .. } => instance,
+        Session::Ssh { instance, .. } => instance,
+        Session::Shells(_) => return Err(SessionError::TmuxRequired.into()),
+    };
+    let entry = HistoryEntry::new(instance.get_instance_id());
+    History::save(entry)?;
+    let mut command = ssm_command(instance, &session_command, endpoint_url)?;
+    match &session {
+        Session::Shell(_) | Session::Document { .. } | Session::Shells(_) => {
+            if let Some((_, Some(document))) = shells.first() {
+                add_document_args(&mut command, document);
+            }
+        }
+        // Nothing may be printed, stdout belongs to ssh
+        Session::Ssh { port, .. } => {
+            command.args([
+                "--document-name",
+                "AWS-StartSSHSession",
+                "--parameters",
+                &format!("portNumber={}", port),
+            ]);
+        }
+        Session::PortForward {
+            remote_host: None,
+            remote_port,
+            local_port,
+            ..
+        } => {
+            println!(
+                "Forwarding localhost:{} to port {} of {} ({}), Ctrl+C to stop",
+                local_port,
+                remote_port,
+                instance.get_name(),
+                instance.get_instance_id()
+            );
+            command.args([
+                "--document-name",
+                "AWS-StartPortForwardingSession",
+                "--parameters",
+                &serde_json::json!({
+                    "portNumber": [remote_port.to_string()],
+                    "localPortNumber": [local_port.to_string()],
+                })
+                .to_string(),
+            ]);
+        }
+        Session::PortForward {
+            remote_host: Some(remote_host),
+            remote_port,
+            local_port,
+            ..
+        } => {
+            println!(
+                "Forwarding localhost:{} to {}:{} through {} ({}), Ctrl+C to stop",
+                local_port,
+                remote_host,
+                remote_port,
+                instance.get_name(),
+                instance.get_instance_id()
+            );
+            command.args([
+                "--document-name",
+                "AWS-StartPortForwardingSessionToRemoteHost",
+                "--parameters",
+                &serde_json::json!({
+                    "host": [remote_host],
+                    "portNumber": [remote_port.to_string()],
+                    "localPortNumber": [local_port.to_string()],
+                })
+                .to_string(),
+            ]);
+        }
+    }
+    let mut child = command.spawn()?;
+
+    // Catch SIGINT, SIGSTP signal and do nothing
+    // So that actually ctrl+c / ctrl+z works on the aws ssm session instead of killing / stopping us
+    let mut _signals = Signals::new([SIGINT, SIGTSTP])?;
+
+    Ok(Some(child.wait()?))
+}
+
+/// Opens a shell per instance, with its document if any, in new tmux windows, or in tiled
+/// panes of a single new window
+fn open_in_tmux(
+    shells: &[(InstanceInfo, Option<SessionDocument>)],
+    session_command: &Option<String>,
+    endpoint_url: &Option<String>,
+    layout: TmuxLayout,
+) -> Result<()> {
+    let mut window: Option<String> = None;
+    for (instance, document) in shells {
+        let entry = HistoryEntry::new(instance.get_instance_id());
+        History::save(entry)?;
+        let name = match instance.get_name() {
+            name if name.is_empty() => instance.get_instance_id(),
+            name => name,
+        };
+        let mut command = Command::new("tmux");
+        match (&window, layout) {
+            (Some(window), TmuxLayout::Panes | TmuxLayout::SynchronizedPanes) => {
+                command.args(["split-window", "-t", window]);
+            }
+            _ => {
+                command.args(["new-window", "-n", &name]);
+            }
+        }
+        command.args(["-P", "-F", "#{window_id} #{pane_id}"]);
+        // Several arguments are run without a shell, so nothing needs quoting
+        let mut session = ssm_command(instance, session_command, endpoint_url)?;
+        if let Some(document) = document {
+            add_document_args(&mut session, document);
+        }
+        let env_file = write_aws_env()?;
+        command
+            .args(["sh", "-c", SOURCE_ENV_SCRIPT, "sh"])
+            .arg(&env_file)
+            .arg(session.get_program())
+            .args(session.get_args());
+        let created = run_tmux(&mut command).inspect_err(|_| {
+            let _ = std::fs::remove_file(&env_file);
+        })?;
+        let (window_id, pane_id) = created.split_once(' ').unwrap_or_default();
+        keep_failed_pane(pane_id)?;
+        run_tmux(Command::new("tmux").args(["select-pane", "-t", pane_id, "-T", &name]))?;
+        if layout != TmuxLayout::Windows {
+            run_tmux(Command::new("tmux").args(["select-layout", "-t", window_id, "tiled"]))?;
+            window = Some(window_id.to_string());
+        }
+    }
+    if let Some(window) = &window
+        && shells.len() > 1
+    {
+        let title = format!("ssm ({})", shells.len());
+        run_tmux(Command::new("tmux").args(["rename-window", "-t", window, &title]))?;
+        run_tmux(Command::new("tmux").args([
+            "set-window-option",
+            "-t",
+            window,
+            "pane-border-status",
+            "top",
+        ]))?;
+        if layout == TmuxLayout::SynchronizedPanes {
+            run_tmux(Command::new("tmux").args([
+                "set-window-option",
+                "-t",
+                window,
+                "synchronize-panes",
+                "on",
+            ]))?;
+        }
+    }
+    Ok(())
+}
+
+/// Sources the file of `write_aws_env`, deletes it and runs the rest of the arguments
+const SOURCE_ENV_SCRIPT: &str = r#"env_file=$1; shift; . "$env_file"; rm -f "$env_file"; exec "$@""#;
+
+/// Writes the AWS variables of sm_connect to a file only the user can read, for a tmux pane
+/// to source. The pane starts in the environment of the tmux server, which may have other
+/// credentials, and values passed on the tmux command line would show in `ps`.
+fn write_aws_env() -> Result<PathBuf> {
+    static FILES: AtomicUsize = AtomicUsize::new(0);
+    let path = std::env::temp_dir().join(format!(
+        "sm_connect_env_{}_{}",
+        std::process::id(),
+        FILES.fetch_add(1, Ordering::Relaxed)
+    ));
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(&path)?;
+    let variables = std::env::vars().filter(|(key, _)| {
+        key.starts_with("AWS_") && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    });
+    for (key, value) in variables {
+        writeln!(file, "export {}='{}'", key, value.replace('\'', "'\\''"))?;
+    }
+    Ok(path)
+}
+
+/// Keeps the pane open when its session fails, so the error can be read. tmux before 3.3
+/// can't tell failures apart, and keeps every pane.
+fn keep_failed_pane(pane_id: &str) -> Result<()> {
+    let set = |value: &str| {
+        run_tmux(Command::new("tmux").args([
+            "set-option",
+            "-p",
+            "-t",
+            pane_id,
+            "remain-on-exit",
+            value,
+        ]))
+    };
+    set("failed").or_else(|_| set("on"))?;
+    Ok(())
+}
+
+/// Runs a tmux command, returning what it printed
+fn run_tmux(command: &mut Command) -> Result<String> {
+    let output = command.output()?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err(SessionError::Tmux(stderr).into());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Runs `ssh` to the instance as `user`, with the ephemeral key pushed to it and the
+/// connection carried by `proxy_command`
+pub fn start_ssh(
+    instance: &InstanceInfo,
+    user: &str,
+    port: u16,
+    key: &EphemeralKey,
+    proxy_command: &str,
+    ssh_args: &[String],
+) -> Result<()> {
+    let entry = HistoryEntry::new(instance.get_instance_id());
+    History::save(entry)?;
+    let mut child = Command::new("ssh")
+        .arg("-i")
+        .arg(key.get_private_key_path())
+        .args([
+            "-o",
+            "IdentitiesOnly=yes",
+            "-o",
+            &format!("ProxyCommand={}", proxy_command),
+            "-p",
+            &port.to_string(),
+            &format!("{}@{}", user, instance.get_instance_id()),
+        ])
+        .args(ssh_args)
+        .spawn()?;
+
+    // ssh handles ctrl+c / ctrl+z itself
+    let mut _signals = Signals::new([SIGINT, SIGTSTP])?;
+
+    child.wait()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_plain_words() {
+        assert_eq!(
+            split_command_template("aws  ssm start-session --target {instance_id}").unwrap(),
+            ["aws", "ssm", "start-session", "--target", "{instance_id}"]
+        );
+    }
+
+    #[test]
+    fn split_quoted_words() {
+        assert_eq!(
+            split_command_template(r#"sh -c 'exec aws "$@"' "" --profile "{profile}""#).unwrap(),
+            ["sh", "-c", r#"exec aws "$@""#, "", "--profile", "{profile}"]
+        );
+    }
+
+    #[test]
+    fn split_invalid_templates() {
+        assert!(split_command_template("aws 'ssm").is_err());
+        assert!(split_command_template("   ").is_err());
+    }
+}
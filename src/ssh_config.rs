@@ -0,0 +1,134 @@
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use home::home_dir;
+
+use crate::aws::InstanceInfo;
+
+/// Host alias of an instance in `~/.ssh/config`: its Name tag, without the whitespace ssh
+/// doesn't allow in host patterns
+pub fn host_alias(name: &str) -> String {
+    name.split_whitespace().collect::<Vec<_>>().join("-")
+}
+
+/// The `Host` entries of the instances, all going through `proxy_command`.
+/// Instances without a Name tag are left out, names used twice get the instance id appended.
+pub fn render_hosts(
+    instances: &[InstanceInfo],
+    proxy_command: &str,
+    prefix: &str,
+    user: &Option<String>,
+) -> String {
+    let mut hosts = String::new();
+    for instance in instances {
+        let alias = host_alias(&instance.get_name());
+        if alias.is_empty() {
+            continue;
+        }
+        let duplicated = instances
+            .iter()
+            .filter(|other| host_alias(&other.get_name()) == alias)
+            .count()
+            > 1;
+        let alias = match duplicated {
+            true => format!("{}{}-{}", prefix, alias, instance.get_instance_id()),
+            false => format!("{}{}", prefix, alias),
+        };
+        // ssh hands the HostName to the ProxyCommand as %h
+        let _ = writeln!(hosts, "Host {}", alias);
+        let _ = writeln!(hosts, "    HostName {}", instance.get_instance_id());
+        if let Some(user) = user {
+            let _ = writeln!(hosts, "    User {}", user);
+        }
+        let _ = writeln!(hosts, "    ProxyCommand {}", proxy_command);
+    }
+    hosts
+}
+
+/// Replaces the block between the `marker` lines of the file with `hosts`, or appends it.
+/// The rest of the file is left untouched.
+pub fn write_block(path: &Path, marker: &str, hosts: &str) -> Result<()> {
+    let begin = format!("# BEGIN {}", marker);
+    let end = format!("# END {}", marker);
+    let block = format!("{}\n{}{}\n", begin, hosts, end);
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e.into()),
+    };
+    let contents = match (contents.find(&begin), contents.find(&end)) {
+        (Some(start), Some(stop)) if start < stop => {
+            let stop = stop + end.len();
+            let stop = stop + contents[stop..].starts_with('\n') as usize;
+            format!("{}{}{}", &contents[..start], block, &contents[stop..])
+        }
+        _ if contents.is_empty() => block,
+        _ if contents.ends_with('\n') => format!("{}\n{}", contents, block),
+        _ => format!("{}\n\n{}", contents, block),
+    };
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, contents)?;
+    Ok(())
+}
+
+pub fn get_ssh_config_path() -> Result<PathBuf> {
+    let Some(home_dir) = home_dir() else {
+        return Result::Err(anyhow::anyhow!("Could not find home directory"));
+    };
+    Ok(home_dir.join(".ssh").join("config"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir()
+            .join(format!("sm_connect_ssh_test_{}", std::process::id()))
+            .join(name)
+    }
+
+    #[test]
+    fn aliases_have_no_whitespace() {
+        assert_eq!(host_alias("web-1"), "web-1");
+        assert_eq!(host_alias(" web  server\t1 "), "web-server-1");
+        assert_eq!(host_alias(""), "");
+    }
+
+    #[test]
+    fn block_is_appended_then_replaced() {
+        let path = temp_path("append");
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, "Host github.com\n    User git").unwrap();
+
+        write_block(&path, "sm_connect default eu-west-1", "Host web-1\n").unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "Host github.com\n    User git\n\n\
+             # BEGIN sm_connect default eu-west-1\nHost web-1\n# END sm_connect default eu-west-1\n"
+        );
+
+        write_block(&path, "sm_connect prod us-east-1", "Host bastion\n").unwrap();
+        write_block(&path, "sm_connect default eu-west-1", "Host web-2\n").unwrap();
+        let contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            contents,
+            "Host github.com\n    User git\n\n\
+             # BEGIN sm_connect default eu-west-1\nHost web-2\n# END sm_connect default eu-west-1\n\n\
+             # BEGIN sm_connect prod us-east-1\nHost bastion\n# END sm_connect prod us-east-1\n"
+        );
+    }
+
+    #[test]
+    fn block_creates_the_file() {
+        let path = temp_path("new/config");
+        write_block(&path, "sm_connect", "Host web-1\n").unwrap();
+        let contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+        assert_eq!(contents, "# BEGIN sm_connect\nHost web-1\n# END sm_connect\n");
+    }
+}
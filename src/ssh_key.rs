@@ -0,0 +1,59 @@
+use std::fs::DirBuilder;
+use std::os::unix::fs::DirBuilderExt;
+use std::path::PathBuf;
+use std::process::Command;
+
+use anyhow::Result;
+use thiserror::Error;
+
+use crate::history::get_current_time;
+
+#[derive(Error, Debug)]
+pub enum SshKeyError {
+    #[error("ssh-keygen failed: {0}")]
+    Keygen(String),
+}
+
+/// ed25519 key pair made for a single connection, deleted once dropped
+#[derive(Debug)]
+pub struct EphemeralKey {
+    dir: PathBuf,
+}
+
+impl EphemeralKey {
+    /// Runs `ssh-keygen` in a directory only the user can read
+    pub fn generate() -> Result<EphemeralKey> {
+        let dir = std::env::temp_dir().join(format!(
+            "sm_connect-{}-{}",
+            std::process::id(),
+            get_current_time()
+        ));
+        DirBuilder::new().mode(0o700).create(&dir)?;
+        let key = EphemeralKey { dir };
+        let output = Command::new("ssh-keygen")
+            .args(["-q", "-t", "ed25519", "-N", "", "-C", "sm_connect", "-f"])
+            .arg(key.get_private_key_path())
+            .output()?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+            return Err(SshKeyError::Keygen(stderr).into());
+        }
+        Ok(key)
+    }
+
+    pub fn get_private_key_path(&self) -> PathBuf {
+        self.dir.join("id_ed25519")
+    }
+
+    /// The public key, in the `ssh-ed25519 AAAA... comment` format
+    pub fn get_public_key(&self) -> Result<String> {
+        let public_key = std::fs::read_to_string(self.dir.join("id_ed25519.pub"))?;
+        Ok(public_key.trim().to_string())
+    }
+}
+
+impl Drop for EphemeralKey {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
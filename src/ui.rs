@@ -25,3 +25,17 @@ pub fn restore_terminal(terminal: &mut Terminal<CrosstermBackend<Stdout>>) -> Re
         .context("unable to switch to main screen")?;
     terminal.show_cursor().context("unable to show cursor")
 }
+
+/// Hand the terminal back to a child process, keeping the `Terminal` around so the TUI can
+/// be brought back with [`resume_terminal`].
+pub fn suspend_terminal(terminal: &mut Terminal<CrosstermBackend<Stdout>>) -> Result<()> {
+    restore_terminal(terminal)
+}
+
+/// Take the terminal back after [`suspend_terminal`] and force a full redraw.
+pub fn resume_terminal(terminal: &mut Terminal<CrosstermBackend<Stdout>>) -> Result<()> {
+    enable_raw_mode().context("failed to enable raw mode")?;
+    execute!(terminal.backend_mut(), EnterAlternateScreen)
+        .context("unable to enter alternate screen")?;
+    terminal.clear().context("unable to clear terminal")
+}